@@ -1,32 +1,114 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use imdbsql::{Dataset, ErrorPolicy, ImportOptions, Table};
 
 #[derive(Parser)]
 #[command(
     author,
     version,
     about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
     long_about = r#"Parse and convert IMDb TSV (Tab Saparated Values) Into a Sqlite tablesthis tool is to be used for educational and personal use only.
 
-This program require IMDb dataset in their original names, format and unzipped. You can find it at https://developer.imdb.com/non-commercial-datasets/ free of charge. To use the dataset you need to comply with their Non-Commercial liecense, otherwise this program is not complicit in any lisence breaking.
+This program require IMDb dataset in their original names and format, either unzipped (.tsv) or as the original gzip archives (.tsv.gz). You can find it at https://developer.imdb.com/non-commercial-datasets/ free of charge. To use the dataset you need to comply with their Non-Commercial liecense, otherwise this program is not complicit in any lisence breaking.
 
 The dataset information can be found at IMDb official site. The tables are separated into 3 categories, core, joining, extra. The core tables are the two main ones (title, name) which requires no foreign keys. Joining tables which will have foreign key to one or both of the core tables. The extra is also a joining tables but they are really slow to parse and contains over 80 mill rows each.
 
-The options below can be toggled at the same time to mix and match to your liking. You can choose per category, stand alone titles, or full, lite and extra versions which is pre-defined tables the rows which have their foreign keys constrait not found will be skipped from being inserted with no error shown. Otherwise errors will be showend and the insertion will stop, example: Trying to insert another row with the same primary key and previous one.
+The options below can be toggled at the same time to mix and match to your liking. You can choose per category, stand alone titles, or full, lite and extra versions which is pre-defined tables the rows which have their foreign keys constrait not found will be skipped from being inserted, and the number of skipped rows is shown per table. The core tables are always parsed before the joining tables, and a joining table is skipped when a core table it references does not exist yet. Otherwise errors will be showend and the insertion will stop, example: Trying to insert another row with the same primary key and previous one.
 
-Make sure to choose the overwrite option if you want to insert the same tables again otherwise you will be having duplicate entries for the joining tables with no primary keys."#
+Make sure to choose the overwrite option if you want to insert the same tables again otherwise you will be having duplicate entries for the joining tables with no primary keys. To refresh an existing database with a newer dump, choose the update option instead."#
 )]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// File name of the database, if file doesn't exist, then file will be created
-    pub path: String,
+    #[arg(required = true)]
+    pub path: Option<String>,
+
+    #[command(flatten)]
+    pub files: DatasetFiles,
+
+    #[command(flatten)]
+    pub table_names: TableNameArgs,
 
     /// Log option to show insertion errors, examples: Insertion errors due to forgein key constraint, or primary key already exists. By default those errors are skiped and not printed out.
     #[arg(long = "log")]
     pub log: bool,
 
+    /// Batch size option sets how many rows are inserted together in a single statement.
+    #[arg(
+        short = 'b',
+        long = "batch-size",
+        value_name = "ROWS",
+        default_value_t = 1000
+    )]
+    pub batch_size: usize,
+
+    /// Fast option tunes SQLite for a one-shot bulk import (in-memory journal, no syncing, larger cache, exclusive lock). Safe settings are restored once done, but a crash during the import can corrupt the database.
+    #[arg(long = "fast", conflicts_with = "safe")]
+    pub fast: bool,
+
+    /// Safe option imports through a write-ahead log with a larger cache, keeping the database consistent if the import is interrupted.
+    #[arg(long = "safe")]
+    pub safe: bool,
+
+    /// Vacuum option will rebuild the database file once the import is done, reclaiming the space left by dropped tables.
+    #[arg(long = "vacuum")]
+    pub vacuum: bool,
+
+    /// No_Indexes option will skip creating the indexes on the foreign key and name columns, which are otherwise built once the tables are parsed.
+    #[arg(long = "no-indexes")]
+    pub no_indexes: bool,
+
     /// Overwrite option will the tables before being inserted into. Will only erase the tables that are toggle through the table flags.
     #[arg(short = 'o', long = "overwrite")]
     pub overwrite: bool,
 
+    /// Imdb ids option will keep the original tconst and nconst identifiers as unique text columns of the title and name tables,
+    /// and create views exposing them next to every joining table, named after the table with an `_imdb` suffix.
+    #[arg(long = "imdb-ids")]
+    pub imdb_ids: bool,
+
+    /// Update option will upsert a fresh dump into an existing database. Title and name rows are updated in place and the ones missing from the dump are deleted,
+    /// along with the joining table rows referencing them, the update failing when rows of other tables still reference them. The joining table rows of every title or name found in their dataset are replaced by the new ones,
+    /// and the rows of the titles that have no line left in title.akas, title.crew, title.episode, title.principals or title.ratings are deleted.
+    #[arg(long = "update", conflicts_with = "overwrite")]
+    pub update: bool,
+
+    /// Snapshot ratings option will append the ratings parsed into the title_rating table to the title_rating_history table as well,
+    /// dated by the modification date of title.ratings.tsv or the snapshot date option. Taking a snapshot again on the same date replaces it.
+    #[arg(long = "snapshot-ratings")]
+    pub snapshot_ratings: bool,
+
+    /// Snapshot date option sets the date (YYYY-MM-DD) of the ratings snapshot instead of the modification date of title.ratings.tsv.
+    #[arg(
+        long = "snapshot-date",
+        value_name = "DATE",
+        requires = "snapshot_ratings"
+    )]
+    pub snapshot_date: Option<String>,
+
+    /// On error option decides what happens to lines that fail to parse and rows that fail to insert, including the rows whose foreign keys are not found,
+    /// and to the title.basics lines read by --exclude-adult to collect the adult titles. Quarantined lines are written to the _rejects table (file_name, line_number, table_name, raw_line, reason).
+    #[arg(long = "on-error", value_enum, default_value_t = ErrorPolicy::Skip)]
+    pub on_error: ErrorPolicy,
+
+    /// Report option writes the end of run report, listing per table the lines read, rows inserted, rows skipped per reason, duration and rows per second,
+    /// as JSON into the given file. The report is printed either way.
+    #[arg(long = "report", value_name = "FILE")]
+    pub report: Option<String>,
+
+    /// Quiet option hides the steps, progress bars and errors printed during the import, only the report and the outcome are printed.
+    #[arg(short = 'q', long = "quiet")]
+    pub quiet: bool,
+
+    /// Exclude adult option will skip the titles flagged as adult, along with every row of the joining tables that references them.
+    ///
+    /// File required (title.basics.tsv)
+    #[arg(long = "exclude-adult")]
+    pub exclude_adult: bool,
+
     /// Lite option will toggle the core tables and the one joining table between them (title, name, name_title)
     #[arg(short = 'l', long = "lite")]
     pub lite: bool,
@@ -39,7 +121,7 @@ pub struct Args {
 
     /// All option will toggle all the tables except the slowest ones (not really all but almost all) which are (title_job, title_character)
     ///
-    /// Files required (title.basics.tsv, title.ratings.tsv, title.crew.tsv, title.episode.tsv, name.basics.tsv)
+    /// Files required (title.basics.tsv, title.akas.tsv, title.ratings.tsv, title.crew.tsv, title.episode.tsv, name.basics.tsv)
     #[arg(short = 'a', long = "all")]
     pub full: bool,
 
@@ -53,7 +135,7 @@ pub struct Args {
     ///
     /// File required (name.bascis.tsv)
     ///
-    /// schema: (id PRIMARY KEY, name TEXT NOT NULL, birth_year INTEGER, death_year INTEGER)
+    /// schema: (id PRIMARY KEY, name TEXT NOT NULL, birth_year INTEGER, death_year INTEGER, nconst TEXT NOT NULL UNIQUE with --imdb-ids)
     #[arg(long = "name")]
     pub name: bool,
 
@@ -69,7 +151,7 @@ pub struct Args {
     ///
     /// File required (title.basics.tsv)
     ///
-    /// schema: (id INTEGER PRIMARY KEY, primary_name TEXT NOT NULL, original_name TEXT NOT NULL, title_type TEXT NOT NULL, release_date INTEGER, end_date INTEGER)
+    /// schema: (id INTEGER PRIMARY KEY, primary_name TEXT NOT NULL, original_name TEXT NOT NULL, title_type TEXT NOT NULL, release_date INTEGER, end_date INTEGER, is_adult BOOLEAN NOT NULL, runtime_minutes INTEGER, tconst TEXT NOT NULL UNIQUE with --imdb-ids)
     #[arg(long = "title")]
     pub title: bool,
 
@@ -89,6 +171,18 @@ pub struct Args {
     #[arg(long = "title_genre")]
     pub title_genre: bool,
 
+    /// Title_Aka option will toggle the title_aka table parsing, along with its title_aka_type and title_aka_attribute tables.
+    ///
+    /// File required (title.akas.tsv)
+    ///
+    /// schema: (title_id INTEGER NOT NULL, ordering INTEGER NOT NULL, title TEXT NOT NULL, region TEXT, language TEXT, is_original_title BOOLEAN NOT NULL, PRIMARY KEY(title_id, ordering), FOREIGN KEY(title_id) REFERENCES title(id))
+    ///
+    /// schema: (title_id INTEGER NOT NULL, ordering INTEGER NOT NULL, type TEXT NOT NULL, FOREIGN KEY(title_id, ordering) REFERENCES title_aka(title_id, ordering))
+    ///
+    /// schema: (title_id INTEGER NOT NULL, ordering INTEGER NOT NULL, attribute TEXT NOT NULL, FOREIGN KEY(title_id, ordering) REFERENCES title_aka(title_id, ordering))
+    #[arg(long = "title_aka")]
+    pub title_aka: bool,
+
     /// Title_Rating option will toggle the name_title table parsing.
     ///
     /// File required (title.ratings.tsv)
@@ -125,7 +219,7 @@ pub struct Args {
     ///
     /// File required (title.principals.tsv)
     ///
-    /// schema: (title_id INTEGER NOT NULL, name_id INTEGER NOT NULL, category TEXT NOT NULL, job TEXT, FOREIGN KEY(title_id) REFERENCES title(id), FOREIGN KEY(name_id) REFERENCES name(id))
+    /// schema: (title_id INTEGER NOT NULL, ordering INTEGER NOT NULL, name_id INTEGER NOT NULL, category TEXT NOT NULL, job TEXT, PRIMARY KEY(title_id, ordering), FOREIGN KEY(title_id) REFERENCES title(id), FOREIGN KEY(name_id) REFERENCES name(id))
     #[arg(long = "title_job")]
     pub title_job: bool,
 
@@ -133,7 +227,209 @@ pub struct Args {
     ///
     /// File required (title.principals.tsv)
     ///
-    /// schema: (title_id INTEGER NOT NULL, name_id INTEGER NOT NULL, character TEXT TEXT NOT NULL, FOREIGN KEY(title_id) REFERENCESE title(id), FOREIGN KEY(name_id) REFERENCES name(id))
+    /// schema: (title_id INTEGER NOT NULL, ordering INTEGER NOT NULL, name_id INTEGER NOT NULL, character TEXT NOT NULL, PRIMARY KEY(title_id, ordering, character), FOREIGN KEY(title_id) REFERENCES title(id), FOREIGN KEY(name_id) REFERENCES name(id))
     #[arg(long = "title_character")]
     pub title_character: bool,
 }
+
+impl Args {
+    /// Tables toggled through the table flags and presets, in the order they should be parsed.
+    pub fn selected_tables(&self) -> Vec<Table> {
+        Table::ALL
+            .into_iter()
+            .filter(|table| match table {
+                Table::Name | Table::Title => {
+                    self.full || self.lite || self.core || self.is_toggled(*table)
+                }
+                Table::NameTitle => self.full || self.lite || self.name_title,
+                Table::TitleJob | Table::TitleCharacter => self.extra || self.is_toggled(*table),
+                _ => self.full || self.is_toggled(*table),
+            })
+            .collect()
+    }
+
+    fn is_toggled(&self, table: Table) -> bool {
+        match table {
+            Table::Name => self.name,
+            Table::NameProfession => self.name_profession,
+            Table::Title => self.title,
+            Table::NameTitle => self.name_title,
+            Table::TitleGenre => self.title_genre,
+            Table::TitleAka => self.title_aka,
+            Table::TitleRating => self.title_rating,
+            Table::TitleDirector => self.title_director,
+            Table::TitleWriter => self.title_writer,
+            Table::TitleEpisode => self.title_episode,
+            Table::TitleJob => self.title_job,
+            Table::TitleCharacter => self.title_character,
+        }
+    }
+
+    /// Options of the import selected through the command line.
+    pub fn import_options(&self) -> ImportOptions {
+        let options = ImportOptions::new()
+            .tables(self.selected_tables())
+            .log(self.log)
+            .progress(!self.quiet)
+            .arguments(std::env::args().skip(1))
+            .batch_size(self.batch_size)
+            .fast(self.fast)
+            .safe(self.safe)
+            .vacuum(self.vacuum)
+            .indexes(!self.no_indexes)
+            .overwrite(self.overwrite)
+            .imdb_ids(self.imdb_ids)
+            .update(self.update)
+            .snapshot_ratings(self.snapshot_ratings)
+            .on_error(self.on_error)
+            .exclude_adult(self.exclude_adult);
+        let options = match &self.snapshot_date {
+            Some(date) => options.snapshot_date(date),
+            None => options,
+        };
+
+        self.table_names.apply(self.files.apply(options))
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Compare a freshly downloaded dataset against an existing database, and record the added, removed and changed
+    /// titles, names, ratings and episodes into the changes table. Run it before updating the database with the new dataset.
+    Diff(DiffArgs),
+}
+
+#[derive(clap::Args)]
+pub struct DiffArgs {
+    /// File name of the database to compare the dataset against
+    pub path: String,
+
+    #[command(flatten)]
+    pub files: DatasetFiles,
+
+    #[command(flatten)]
+    pub table_names: TableNameArgs,
+
+    /// Date recorded along with every change, defaults to the current date
+    #[arg(long = "snapshot-date", value_name = "DATE")]
+    pub snapshot_date: Option<String>,
+
+    /// Hides the steps and progress bars of the comparison
+    #[arg(short = 'q', long = "quiet")]
+    pub quiet: bool,
+}
+
+impl DiffArgs {
+    /// Options of the comparison, only the dataset files, table names and snapshot date are used.
+    pub fn import_options(&self) -> ImportOptions {
+        let options = self
+            .table_names
+            .apply(self.files.apply(ImportOptions::new().progress(!self.quiet)));
+        match &self.snapshot_date {
+            Some(date) => options.snapshot_date(date),
+            None => options,
+        }
+    }
+}
+
+/// Location of the IMDb dataset files.
+#[derive(clap::Args)]
+pub struct DatasetFiles {
+    /// Directory containing the IMDb dataset files
+    #[arg(
+        short = 'i',
+        long = "input-dir",
+        value_name = "DIR",
+        default_value = "."
+    )]
+    pub input_dir: String,
+
+    /// Path to title.basics.tsv, overrides the file found in the input directory
+    #[arg(long = "title-basics", value_name = "PATH")]
+    pub title_basics_file: Option<String>,
+
+    /// Path to title.akas.tsv, overrides the file found in the input directory
+    #[arg(long = "title-akas", value_name = "PATH")]
+    pub title_akas_file: Option<String>,
+
+    /// Path to title.ratings.tsv, overrides the file found in the input directory
+    #[arg(long = "title-ratings", value_name = "PATH")]
+    pub title_ratings_file: Option<String>,
+
+    /// Path to title.crew.tsv, overrides the file found in the input directory
+    #[arg(long = "title-crew", value_name = "PATH")]
+    pub title_crew_file: Option<String>,
+
+    /// Path to title.episode.tsv, overrides the file found in the input directory
+    #[arg(long = "title-episode", value_name = "PATH")]
+    pub title_episode_file: Option<String>,
+
+    /// Path to title.principals.tsv, overrides the file found in the input directory
+    #[arg(long = "title-principals", value_name = "PATH")]
+    pub title_principals_file: Option<String>,
+
+    /// Path to name.basics.tsv, overrides the file found in the input directory
+    #[arg(long = "name-basics", value_name = "PATH")]
+    pub name_basics_file: Option<String>,
+}
+
+impl DatasetFiles {
+    /// Points the options at the dataset files.
+    pub fn apply(&self, options: ImportOptions) -> ImportOptions {
+        let files = [
+            (Dataset::TitleBasics, &self.title_basics_file),
+            (Dataset::TitleAkas, &self.title_akas_file),
+            (Dataset::TitleRatings, &self.title_ratings_file),
+            (Dataset::TitleCrew, &self.title_crew_file),
+            (Dataset::TitleEpisode, &self.title_episode_file),
+            (Dataset::TitlePrincipals, &self.title_principals_file),
+            (Dataset::NameBasics, &self.name_basics_file),
+        ];
+
+        files.into_iter().fold(
+            options.input_dir(&self.input_dir),
+            |options, file| match file {
+                (dataset, Some(path)) => options.dataset_file(dataset, path),
+                (_, None) => options,
+            },
+        )
+    }
+}
+
+/// Names the tables are given in the database.
+#[derive(clap::Args)]
+pub struct TableNameArgs {
+    /// Table prefix option prepends the prefix to the name of every table parsed from the dataset, along with the tables derived from them, their indexes and views, and the changes, _rejects and _imdbsql_meta tables
+    #[arg(long = "table-prefix", value_name = "PREFIX")]
+    pub prefix: Option<String>,
+
+    /// Table name option gives a table another name, as TABLE=NAME (example: title=imdb_movie), the prefix is not prepended to it. Can be repeated, as long as every table keeps a name of its own
+    #[arg(long = "table-name", value_name = "TABLE=NAME", value_parser = parse_table_name)]
+    pub renames: Vec<(Table, String)>,
+}
+
+impl TableNameArgs {
+    /// Applies the prefix and the renames to the options.
+    pub fn apply(&self, options: ImportOptions) -> ImportOptions {
+        let options = match &self.prefix {
+            Some(prefix) => options.table_prefix(prefix),
+            None => options,
+        };
+
+        self.renames.iter().fold(options, |options, (table, name)| {
+            options.table_name(*table, name)
+        })
+    }
+}
+
+fn parse_table_name(value: &str) -> Result<(Table, String), String> {
+    let (table, name) = value
+        .split_once('=')
+        .ok_or(format!("{value} has to be given as TABLE=NAME"))?;
+    let table = Table::ALL
+        .into_iter()
+        .find(|t| t.name() == table)
+        .ok_or(format!("{table} is not one of the tables"))?;
+
+    Ok((table, name.to_string()))
+}
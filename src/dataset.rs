@@ -0,0 +1,163 @@
+/// The IMDb dataset files the tables are parsed from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dataset {
+    TitleBasics,
+    NameBasics,
+    TitleAkas,
+    TitleRatings,
+    TitleCrew,
+    TitleEpisode,
+    TitlePrincipals,
+}
+
+impl Dataset {
+    /// Every dataset in the order they are parsed, title.basics comes first as name_title from
+    /// name.basics references the title table.
+    pub const ALL: [Dataset; 7] = [
+        Dataset::TitleBasics,
+        Dataset::NameBasics,
+        Dataset::TitleAkas,
+        Dataset::TitleRatings,
+        Dataset::TitleCrew,
+        Dataset::TitleEpisode,
+        Dataset::TitlePrincipals,
+    ];
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Dataset::TitleBasics => "title.basics.tsv",
+            Dataset::TitleAkas => "title.akas.tsv",
+            Dataset::TitleRatings => "title.ratings.tsv",
+            Dataset::TitleCrew => "title.crew.tsv",
+            Dataset::TitleEpisode => "title.episode.tsv",
+            Dataset::TitlePrincipals => "title.principals.tsv",
+            Dataset::NameBasics => "name.basics.tsv",
+        }
+    }
+
+    /// Columns the header line of the dataset file has to name, in their documented order.
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Dataset::TitleBasics => &[
+                "tconst",
+                "titleType",
+                "primaryTitle",
+                "originalTitle",
+                "isAdult",
+                "startYear",
+                "endYear",
+                "runtimeMinutes",
+                "genres",
+            ],
+            Dataset::TitleAkas => &[
+                "titleId",
+                "ordering",
+                "title",
+                "region",
+                "language",
+                "types",
+                "attributes",
+                "isOriginalTitle",
+            ],
+            Dataset::TitleRatings => &["tconst", "averageRating", "numVotes"],
+            Dataset::TitleCrew => &["tconst", "directors", "writers"],
+            Dataset::TitleEpisode => &["tconst", "parentTconst", "seasonNumber", "episodeNumber"],
+            Dataset::TitlePrincipals => &[
+                "tconst",
+                "ordering",
+                "nconst",
+                "category",
+                "job",
+                "characters",
+            ],
+            Dataset::NameBasics => &[
+                "nconst",
+                "primaryName",
+                "birthYear",
+                "deathYear",
+                "primaryProfession",
+                "knownForTitles",
+            ],
+        }
+    }
+}
+
+/// The tables this tool can create, listed in the order they are parsed within their dataset.
+/// The core tables come first so the joining tables find their foreign keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Table {
+    Name,
+    Title,
+    NameProfession,
+    NameTitle,
+    TitleGenre,
+    TitleAka,
+    TitleRating,
+    TitleDirector,
+    TitleWriter,
+    TitleEpisode,
+    TitleJob,
+    TitleCharacter,
+}
+
+impl Table {
+    pub const ALL: [Table; 12] = [
+        Table::Name,
+        Table::Title,
+        Table::NameProfession,
+        Table::NameTitle,
+        Table::TitleGenre,
+        Table::TitleAka,
+        Table::TitleRating,
+        Table::TitleDirector,
+        Table::TitleWriter,
+        Table::TitleEpisode,
+        Table::TitleJob,
+        Table::TitleCharacter,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Table::Name => "name",
+            Table::NameProfession => "name_profession",
+            Table::Title => "title",
+            Table::NameTitle => "name_title",
+            Table::TitleGenre => "title_genre",
+            Table::TitleAka => "title_aka",
+            Table::TitleRating => "title_rating",
+            Table::TitleDirector => "title_director",
+            Table::TitleWriter => "title_writer",
+            Table::TitleEpisode => "title_episode",
+            Table::TitleJob => "title_job",
+            Table::TitleCharacter => "title_character",
+        }
+    }
+
+    /// Core tables referenced through foreign keys by this table.
+    pub fn references(&self) -> &'static [Table] {
+        match self {
+            Table::Name | Table::Title => &[],
+            Table::NameProfession => &[Table::Name],
+            Table::TitleGenre | Table::TitleAka | Table::TitleRating | Table::TitleEpisode => {
+                &[Table::Title]
+            }
+            Table::NameTitle
+            | Table::TitleDirector
+            | Table::TitleWriter
+            | Table::TitleJob
+            | Table::TitleCharacter => &[Table::Name, Table::Title],
+        }
+    }
+
+    pub fn dataset(&self) -> Dataset {
+        match self {
+            Table::Name | Table::NameProfession | Table::NameTitle => Dataset::NameBasics,
+            Table::Title | Table::TitleGenre => Dataset::TitleBasics,
+            Table::TitleAka => Dataset::TitleAkas,
+            Table::TitleRating => Dataset::TitleRatings,
+            Table::TitleDirector | Table::TitleWriter => Dataset::TitleCrew,
+            Table::TitleEpisode => Dataset::TitleEpisode,
+            Table::TitleJob | Table::TitleCharacter => Dataset::TitlePrincipals,
+        }
+    }
+}
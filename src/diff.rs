@@ -0,0 +1,481 @@
+use crate::{
+    dataset::Table,
+    error::{ImportError, ParseError},
+    migrations,
+    options::ImportOptions,
+    parsers::{inserter::Value, names, title_episodes, title_ratings, titles},
+    records::{line_id, NameBasics, Record, TitleBasics, TitleEpisode, TitleRating},
+    tsv::{read_header, DatasetHeader},
+    update::{keys_table, record_keys, track_keys},
+    utils::{count_lines, execute_sql, open_dataset, percentage_printer, print_line, table_exists},
+};
+use sqlx::{sqlite::SqliteRow, Connection, Row, SqliteConnection, TypeInfo, ValueRef};
+use std::{io::BufRead, path::Path};
+
+/// How many ids are collected before being recorded into the keys table.
+const KEYS_BATCH: usize = 10000;
+
+/// Table the changes are recorded into.
+pub const CHANGES_TABLE: &str = "changes";
+
+/// A table compared against its dataset, along with the parser turning a line into the values of its columns.
+/// The first column identifies the row.
+struct Entity {
+    table: Table,
+    columns: &'static [&'static str],
+    parse: fn(&DatasetHeader, &str) -> Result<Vec<Value>, ParseError>,
+}
+
+const ENTITIES: [Entity; 4] = [
+    Entity {
+        table: Table::Title,
+        columns: &titles::COLUMNS,
+        parse: |header, line| {
+            TitleBasics::parse_line(header, line).map(|title| title.to_row(false))
+        },
+    },
+    Entity {
+        table: Table::Name,
+        columns: &names::COLUMNS,
+        parse: |header, line| NameBasics::parse_line(header, line).map(|name| name.to_row(false)),
+    },
+    Entity {
+        table: Table::TitleRating,
+        columns: &title_ratings::COLUMNS,
+        parse: |header, line| TitleRating::parse_line(header, line).map(|rating| rating.to_row()),
+    },
+    Entity {
+        table: Table::TitleEpisode,
+        columns: &title_episodes::COLUMNS,
+        parse: |header, line| {
+            TitleEpisode::parse_line(header, line).map(|episode| episode.to_row())
+        },
+    },
+];
+
+/// Compares the datasets against the database, recording every added, removed and changed row into the changes table.
+/// The comparison is recorded as a whole or not at all, and replaces the changes recorded earlier on the same snapshot
+/// date, so it can be run again. Malformed lines are skipped and counted, their rows are not reported as removed,
+/// and no row is when the id of a malformed line can not be read.
+pub async fn run(conn: &mut SqliteConnection, options: &ImportOptions) -> Result<(), ImportError> {
+    migrations::check_diffable(conn, &options.table_names).await?;
+    let snapshot_date = match &options.snapshot_date {
+        Some(date) => date.clone(),
+        None => sqlx::query_scalar("SELECT date('now')")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| ImportError::database("Unable to get the current date", e))?,
+    };
+
+    let mut tx = conn
+        .begin()
+        .await
+        .map_err(|e| ImportError::database("Failed to start transaction", e))?;
+
+    let changes_table = &options.table_names.internal(CHANGES_TABLE);
+    execute_sql(format!("CREATE TABLE IF NOT EXISTS {changes_table} (entity text not null, id integer not null, change_type text not null, old_value text, new_value text, snapshot_date text not null)").as_str(), &mut tx)
+        .await
+        .map_err(|e| ImportError::database(format!("Unable to create {changes_table} table"), e))?;
+
+    let changes = Changes {
+        table_name: changes_table,
+        snapshot_date: &snapshot_date,
+    };
+    for entity in &ENTITIES {
+        let table_name = &options.table_names.get(entity.table);
+        let file_name = options.dataset_path(entity.table.dataset());
+        if !table_exists(table_name, &mut tx).await? {
+            options.print_step(format_args!(
+                "-- Skipping {table_name} as the table does not exist --"
+            ));
+            continue;
+        }
+
+        if !Path::new(&file_name).is_file() {
+            options.print_step(format_args!(
+                "-- Skipping {table_name} as {file_name} does not exist --"
+            ));
+            continue;
+        }
+
+        changes.clear(entity.table.name(), &mut tx).await?;
+        diff_entity(entity, table_name, &file_name, &changes, &mut tx, options).await?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| ImportError::database("Failed to commit transactions", e))?;
+
+    options.print_step("Finished Comparing.");
+    Ok(())
+}
+
+async fn diff_entity(
+    entity: &Entity,
+    table_name: &str,
+    file_name: &str,
+    changes: &Changes<'_>,
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+) -> Result<(), ImportError> {
+    let key = entity.columns[0];
+    let columns = entity.columns.join(", ");
+    let select = format!("SELECT {columns} FROM {table_name} WHERE {key} = $1");
+
+    let count = if options.progress {
+        count_lines(file_name)?
+    } else {
+        0
+    };
+    options.print_step(format_args!(
+        "-- Comparing {count} entries against {table_name} --"
+    ));
+    track_keys(table_name, conn).await?;
+
+    let (mut added, mut changed, mut removed, mut malformed, mut unkeyed) = (0, 0, 0, 0, 0);
+    let mut keys = Vec::with_capacity(KEYS_BATCH);
+    let mut lines = open_dataset(file_name)?.lines();
+    let header = read_header(&mut lines, entity.table.dataset(), file_name)?;
+    for (i, line) in lines.enumerate() {
+        // Line numbers start at 1 and the header line is skipped.
+        let number = i + 2;
+        let line = line.map_err(|e| {
+            ImportError::io(format!("Unable to read line {number} of {file_name}"), e)
+        })?;
+        let values = match (entity.parse)(&header, &line) {
+            Ok(values) => values,
+            Err(_) => {
+                malformed += 1;
+                match line_id(&header, &line) {
+                    Some(id) => keys.push(id.to_string()),
+                    None => unkeyed += 1,
+                }
+                continue;
+            }
+        };
+        let Value::Integer(id) = values[0] else {
+            malformed += 1;
+            unkeyed += 1;
+            continue;
+        };
+
+        let stored = sqlx::query(&select)
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| {
+                ImportError::database(format!("Unable to read {table_name} row {id}"), e)
+            })?;
+
+        match stored.map(|row| stored_values(&row)) {
+            None => {
+                let new_value = render(entity.columns, &values, 1..values.len());
+                changes
+                    .record(
+                        entity.table.name(),
+                        id,
+                        "added",
+                        None,
+                        Some(new_value),
+                        conn,
+                    )
+                    .await?;
+                added += 1;
+            }
+            Some(old) => {
+                let differing: Vec<usize> =
+                    (1..values.len()).filter(|&i| old[i] != values[i]).collect();
+                if !differing.is_empty() {
+                    let old_value = render(entity.columns, &old, differing.iter().copied());
+                    let new_value = render(entity.columns, &values, differing.iter().copied());
+                    changes
+                        .record(
+                            entity.table.name(),
+                            id,
+                            "changed",
+                            Some(old_value),
+                            Some(new_value),
+                            conn,
+                        )
+                        .await?;
+                    changed += 1;
+                }
+            }
+        }
+
+        keys.push(id.to_string());
+        if keys.len() >= KEYS_BATCH {
+            record_keys(table_name, &keys, conn).await?;
+            keys.clear();
+        }
+
+        if options.progress {
+            percentage_printer(i, count);
+        }
+    }
+
+    record_keys(table_name, &keys, conn).await?;
+    if options.progress {
+        print_line("");
+    }
+
+    if unkeyed > 0 {
+        options.print_step(format_args!(
+            "-- Not looking for removed rows of {table_name}, as {unkeyed} malformed lines have no readable id --"
+        ));
+    } else {
+        let vanished = sqlx::query(&format!(
+            "SELECT {columns} FROM {table_name} WHERE {key} NOT IN (SELECT id FROM {})",
+            keys_table(table_name)
+        ))
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| {
+            ImportError::database(format!("Unable to read removed rows of {table_name}"), e)
+        })?;
+
+        for row in vanished {
+            let old = stored_values(&row);
+            let Value::Integer(id) = old[0] else {
+                continue;
+            };
+
+            let old_value = render(entity.columns, &old, 1..old.len());
+            changes
+                .record(
+                    entity.table.name(),
+                    id,
+                    "removed",
+                    Some(old_value),
+                    None,
+                    conn,
+                )
+                .await?;
+            removed += 1;
+        }
+    }
+
+    if malformed > 0 {
+        options.print_step(format_args!(
+            "-- Skipped {malformed} malformed lines of {file_name} --"
+        ));
+    }
+    options.print_step(format_args!(
+        "-- Found {added} added, {removed} removed and {changed} changed rows of {table_name} --"
+    ));
+    Ok(())
+}
+
+/// Reads the columns of a stored row back into values, following the type SQLite stored each value with.
+fn stored_values(row: &SqliteRow) -> Vec<Value> {
+    (0..row.len())
+        .map(|i| {
+            let Ok(raw) = row.try_get_raw(i) else {
+                return Value::Null;
+            };
+
+            if raw.is_null() {
+                return Value::Null;
+            }
+
+            match raw.type_info().name() {
+                "INTEGER" => row.try_get_unchecked(i).map_or(Value::Null, Value::Integer),
+                "REAL" => row.try_get_unchecked(i).map_or(Value::Null, Value::Real),
+                _ => row.try_get_unchecked(i).map_or(Value::Null, Value::Text),
+            }
+        })
+        .collect()
+}
+
+/// Renders the given columns of the row as `column=value` pairs.
+fn render(columns: &[&str], values: &[Value], indexes: impl Iterator<Item = usize>) -> String {
+    indexes
+        .map(|i| format!("{}={}", columns[i], values[i]))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Changes table the differences are recorded into, dated by the snapshot date.
+struct Changes<'a> {
+    table_name: &'a str,
+    snapshot_date: &'a str,
+}
+
+impl Changes<'_> {
+    /// Deletes the changes of the entity recorded on the snapshot date, as they are about to be recorded again.
+    async fn clear(&self, entity: &str, conn: &mut SqliteConnection) -> Result<(), ImportError> {
+        sqlx::query(&format!(
+            "DELETE FROM {} WHERE entity = $1 AND snapshot_date = $2",
+            self.table_name
+        ))
+        .bind(entity)
+        .bind(self.snapshot_date)
+        .execute(conn)
+        .await
+        .map_err(|e| {
+            ImportError::database(
+                format!(
+                    "Unable to clear the changes of {entity} on {}",
+                    self.snapshot_date
+                ),
+                e,
+            )
+        })?;
+
+        Ok(())
+    }
+
+    async fn record(
+        &self,
+        entity: &str,
+        id: i64,
+        change_type: &str,
+        old_value: Option<String>,
+        new_value: Option<String>,
+        conn: &mut SqliteConnection,
+    ) -> Result<(), ImportError> {
+        sqlx::query(&format!(
+            "INSERT INTO {} VALUES ($1, $2, $3, $4, $5, $6)",
+            self.table_name
+        ))
+        .bind(entity)
+        .bind(id)
+        .bind(change_type)
+        .bind(old_value)
+        .bind(new_value)
+        .bind(self.snapshot_date)
+        .execute(conn)
+        .await
+        .map_err(|e| {
+            ImportError::database(format!("Unable to record change of {entity} {id}"), e)
+        })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::run;
+    use crate::{
+        dataset::{Dataset, Table},
+        fixtures::{count, memory_database, rows, Datasets},
+        import::import,
+    };
+    use sqlx::SqliteConnection;
+
+    fn title(id: u32, name: &str) -> String {
+        format!("tt{id:07}\tmovie\t{name}\t{name}\t0\t1894\t\\N\t1\tDrama")
+    }
+
+    /// Database holding the titles 1, 2 and 3, along with the datasets to compare against it.
+    async fn imported_titles(name: &str) -> (Datasets, SqliteConnection) {
+        let datasets = Datasets::new(name);
+        let mut conn = memory_database().await;
+        datasets.write(
+            Dataset::TitleBasics,
+            &[
+                &title(1, "Carmencita"),
+                &title(2, "Le clown"),
+                &title(3, "Pauvre Pierrot"),
+            ],
+        );
+        import(&mut conn, datasets.options().table(Table::Title))
+            .await
+            .unwrap();
+
+        (datasets, conn)
+    }
+
+    #[tokio::test]
+    async fn records_added_removed_and_changed_rows() {
+        let (datasets, mut conn) = imported_titles("diff-changes").await;
+        datasets.write(
+            Dataset::TitleBasics,
+            &[
+                &title(1, "Carmencita"),
+                &title(2, "The Clown"),
+                &title(4, "Un bon bock"),
+            ],
+        );
+
+        run(&mut conn, &datasets.options().snapshot_date("2024-01-01"))
+            .await
+            .unwrap();
+        assert_eq!(
+            rows(
+                &mut conn,
+                "SELECT entity || ' ' || id || ' ' || change_type || ' ' || snapshot_date FROM changes ORDER BY id"
+            )
+            .await,
+            vec![
+                "title 2 changed 2024-01-01",
+                "title 3 removed 2024-01-01",
+                "title 4 added 2024-01-01",
+            ]
+        );
+        assert_eq!(
+            rows(
+                &mut conn,
+                "SELECT old_value || ' -> ' || new_value FROM changes WHERE change_type = 'changed'"
+            )
+            .await,
+            vec!["primary_name=Le clown, original_name=Le clown -> primary_name=The Clown, original_name=The Clown"]
+        );
+    }
+
+    #[tokio::test]
+    async fn replaces_the_changes_of_the_same_date() {
+        let (datasets, mut conn) = imported_titles("diff-again").await;
+        datasets.write(Dataset::TitleBasics, &[&title(1, "Carmencita")]);
+
+        let options = datasets.options().snapshot_date("2024-01-01");
+        run(&mut conn, &options).await.unwrap();
+        run(&mut conn, &options).await.unwrap();
+        assert_eq!(count(&mut conn, "changes").await, 2);
+
+        run(&mut conn, &datasets.options().snapshot_date("2024-01-02"))
+            .await
+            .unwrap();
+        assert_eq!(count(&mut conn, "changes").await, 4);
+    }
+
+    #[tokio::test]
+    async fn skips_malformed_lines() {
+        let (datasets, mut conn) = imported_titles("diff-malformed").await;
+        datasets.write(
+            Dataset::TitleBasics,
+            &[
+                &title(1, "Carmencita"),
+                "tt0000002\tmovie",
+                &title(3, "Pauvre Pierrot"),
+                &title(4, "Un bon bock"),
+            ],
+        );
+
+        run(&mut conn, &datasets.options()).await.unwrap();
+        assert_eq!(
+            rows(
+                &mut conn,
+                "SELECT id || ' ' || change_type FROM changes ORDER BY id"
+            )
+            .await,
+            vec!["4 added"]
+        );
+
+        // Without the id of the malformed line, the missing title 3 can not be told to be removed.
+        datasets.write(
+            Dataset::TitleBasics,
+            &[&title(1, "Carmencita"), "\\N\tmovie", &title(2, "Le clown")],
+        );
+        run(&mut conn, &datasets.options().snapshot_date("2024-01-02"))
+            .await
+            .unwrap();
+        assert!(rows(
+            &mut conn,
+            "SELECT change_type FROM changes WHERE snapshot_date = '2024-01-02'"
+        )
+        .await
+        .is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+use std::{fmt::Display, io, sync::Arc};
+
+/// Error failing an import, or a table of it. Tables failing on their own are listed in the `ImportReport` instead.
+///
+/// The variants tell the callers what went wrong, and the command line exits with the `exit_code` of the variant.
+#[derive(Clone, Debug)]
+pub enum ImportError {
+    /// The options can not be honoured.
+    Options(String),
+    /// A dataset file or a report could not be opened, read or written.
+    Io {
+        message: String,
+        source: Arc<io::Error>,
+    },
+    /// A line of a dataset file is malformed.
+    Parse {
+        file_name: String,
+        /// Line number, the header being the first line.
+        line: usize,
+        /// Column of the value that could not be parsed, starting at 1.
+        column: usize,
+        message: String,
+    },
+    /// The tables of the database can not receive the import, they are missing, of another schema version or
+    /// can not be migrated.
+    Schema(String),
+    /// SQLite failed to run a statement, including when the database is locked.
+    Database {
+        message: String,
+        source: Arc<sqlx::Error>,
+    },
+}
+
+impl ImportError {
+    pub(crate) fn io(message: impl Into<String>, source: io::Error) -> Self {
+        ImportError::Io {
+            message: message.into(),
+            source: Arc::new(source),
+        }
+    }
+
+    pub(crate) fn database(message: impl Into<String>, source: sqlx::Error) -> Self {
+        ImportError::Database {
+            message: message.into(),
+            source: Arc::new(source),
+        }
+    }
+
+    /// Exit code of the command line, distinct for every variant. Clap exits with 2 on invalid arguments as well.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ImportError::Options(_) => 2,
+            ImportError::Io { .. } => 3,
+            ImportError::Parse { .. } => 4,
+            ImportError::Schema(_) => 5,
+            ImportError::Database { .. } => 6,
+        }
+    }
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Options(message) | ImportError::Schema(message) => write!(f, "{message}"),
+            ImportError::Io { message, source } => write!(f, "{message} -> {source}"),
+            ImportError::Parse {
+                file_name,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "Failed to parse line {line} of {file_name} at column {column} -> {message}"
+            ),
+            ImportError::Database { message, source } => write!(f, "{message} -> {source}"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io { source, .. } => Some(source.as_ref()),
+            ImportError::Database { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// A value of a dataset line that could not be parsed, located once the line is known through `at`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Column of the value, starting at 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(column: usize, message: impl Into<String>) -> Self {
+        Self {
+            column,
+            message: message.into(),
+        }
+    }
+
+    /// Locates the error at a line of a dataset file.
+    pub fn at(self, file_name: &str, line: usize) -> ImportError {
+        ImportError::Parse {
+            file_name: file_name.to_string(),
+            line,
+            column: self.column,
+            message: self.message,
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,56 @@
+use crate::{dataset::Dataset, options::ImportOptions, utils::connect};
+use sqlx::SqliteConnection;
+use std::{fs, path::PathBuf, process};
+
+/// Directory of dataset files written by a test, removed once dropped.
+pub struct Datasets {
+    dir: PathBuf,
+}
+
+impl Datasets {
+    /// Empty directory named after the test.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("imdbsql-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self { dir }
+    }
+
+    /// Writes the dataset file, its header line followed by the lines.
+    pub fn write(&self, dataset: Dataset, lines: &[&str]) {
+        let mut text = dataset.columns().join("\t") + "\n";
+        for line in lines {
+            text += line;
+            text += "\n";
+        }
+
+        fs::write(self.dir.join(dataset.file_name()), text).unwrap();
+    }
+
+    /// Options importing from the directory.
+    pub fn options(&self) -> ImportOptions {
+        ImportOptions::new().input_dir(self.dir.to_string_lossy())
+    }
+}
+
+impl Drop for Datasets {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+pub async fn memory_database() -> SqliteConnection {
+    connect("sqlite::memory:").await.unwrap()
+}
+
+/// Rows of the query, every value being rendered as text.
+pub async fn rows(conn: &mut SqliteConnection, query: &str) -> Vec<String> {
+    sqlx::query_scalar(query).fetch_all(conn).await.unwrap()
+}
+
+pub async fn count(conn: &mut SqliteConnection, table_name: &str) -> i64 {
+    sqlx::query_scalar(&format!("SELECT count(*) FROM {table_name}"))
+        .fetch_one(conn)
+        .await
+        .unwrap()
+}
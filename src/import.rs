@@ -0,0 +1,715 @@
+use crate::{
+    dataset::{Dataset, Table},
+    error::ImportError,
+    indexes, meta, migrations,
+    options::{ErrorPolicy, ImportOptions},
+    parsers::{pipeline, rejects, titles},
+    profile,
+    report::{ImportReport, ImportStatus, TableReport},
+    utils::{self, execute_sql},
+    views,
+};
+use sqlx::SqliteConnection;
+use std::{
+    collections::HashSet,
+    time::{Instant, SystemTime},
+};
+
+/// Opens the database, creating the file when it does not exist, with foreign keys enforced.
+pub async fn connect(path: &str) -> Result<SqliteConnection, ImportError> {
+    utils::connect(path).await
+}
+
+/// Imports the selected tables from the datasets into the database.
+///
+/// The import only fails as a whole when the options can not be honoured or the database can not be prepared,
+/// the tables failing on their own are listed in the report along with the statistics of every table.
+pub async fn import(
+    conn: &mut SqliteConnection,
+    mut options: ImportOptions,
+) -> Result<ImportReport, ImportError> {
+    let start = Instant::now();
+    let started_at = SystemTime::now();
+    options.validate()?;
+    options.resolve_snapshot_date()?;
+
+    // Rows missing their parents are only skipped when the foreign keys are enforced.
+    execute_sql("PRAGMA foreign_keys = ON", &mut *conn)
+        .await
+        .map_err(|e| ImportError::database("Unable to enforce foreign keys", e))?;
+
+    let selected = options.selected_tables();
+    let overwritten = if options.overwrite {
+        selected.clone()
+    } else {
+        Vec::new()
+    };
+    migrations::migrate(
+        conn,
+        &selected,
+        &overwritten,
+        &options.table_names,
+        options.progress,
+    )
+    .await?;
+
+    profile::begin_import(conn, &options).await?;
+    let (status, reports) = match import_tables(conn, &options, &selected, started_at).await {
+        Ok(imported) => imported,
+        Err(e) => {
+            // Best effort, the error that stopped the import is the one worth reporting.
+            let _ = profile::restore_pragmas(conn, &options).await;
+            return Err(e);
+        }
+    };
+
+    profile::finish_import(conn, &options).await?;
+    Ok(ImportReport {
+        status,
+        tables: reports,
+        duration: start.elapsed(),
+    })
+}
+
+/// Imports the selected tables once the import profile is applied, returning the status recorded in the metadata
+/// along with the report of every table.
+async fn import_tables(
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+    selected: &[Table],
+    started_at: SystemTime,
+) -> Result<(ImportStatus, Vec<TableReport>), ImportError> {
+    if options.on_error == ErrorPolicy::Quarantine {
+        rejects::create_table(&options.table_names, conn).await?;
+    }
+
+    // Dataset files read by the import, in reading order.
+    let mut datasets: Vec<String> = Vec::new();
+    let excluded_titles = if options.exclude_adult {
+        let file_name = options.dataset_path(Dataset::TitleBasics);
+        let (ids, skipped) = titles::adult_title_ids(&file_name, options.on_error)?;
+        if skipped > 0 {
+            options.print_step(format_args!(
+                "-- Skipped {skipped} malformed lines of {file_name} while collecting the adult titles --"
+            ));
+        }
+        datasets.push(file_name);
+        options.print_step(format_args!("-- Excluding {} adult titles --", ids.len()));
+        ids
+    } else {
+        HashSet::new()
+    };
+
+    let mut loaded = Vec::new();
+    let mut reports = Vec::new();
+    let mut aborted = false;
+    for dataset in Dataset::ALL {
+        let tables: Vec<Table> = selected
+            .iter()
+            .copied()
+            .filter(|table| table.dataset() == dataset)
+            .collect();
+        if tables.is_empty() {
+            continue;
+        }
+
+        let file_name = options.dataset_path(dataset);
+        if !datasets.contains(&file_name) {
+            datasets.push(file_name.clone());
+        }
+
+        let dataset_reports =
+            pipeline::parse_dataset(&file_name, &tables, conn, options, &excluded_titles).await;
+        // Tables parsed from the same file fail together, so their shared error is only printed once.
+        let mut errors: Vec<String> = Vec::new();
+        for report in &dataset_reports {
+            match &report.error {
+                None if !loaded.contains(&report.table) => loaded.push(report.table),
+                Some(e) if !errors.contains(&e.to_string()) => {
+                    options.print_error(e);
+                    errors.push(e.to_string());
+                }
+                _ => {}
+            }
+        }
+
+        let failed = !errors.is_empty();
+        reports.extend(dataset_reports);
+
+        if failed && options.on_error == ErrorPolicy::Abort {
+            aborted = true;
+            break;
+        }
+    }
+
+    if options.indexes {
+        for &table in &loaded {
+            options.print_step(format_args!(
+                "-- Creating indexes on {} --",
+                options.table_names.get(table)
+            ));
+            if let Err(e) = indexes::create_indexes(table, &options.table_names, conn).await {
+                options.print_error(&e);
+            }
+        }
+    }
+
+    if options.imdb_ids {
+        for &table in &loaded {
+            options.print_step(format_args!(
+                "-- Creating views on {} --",
+                options.table_names.get(table)
+            ));
+            if let Err(e) = views::create_views(table, &options.table_names, conn).await {
+                options.print_error(&e);
+            }
+        }
+    }
+
+    let failed = reports.iter().any(|report| report.error.is_some());
+    let status = if aborted {
+        ImportStatus::Aborted
+    } else if failed {
+        ImportStatus::Failed
+    } else {
+        ImportStatus::Ok
+    };
+    meta::record_import(conn, options, started_at, &datasets, &reports, status).await?;
+
+    Ok((status, reports))
+}
+
+/// Compares the datasets against the database, recording every added, removed and changed title, name, rating
+/// and episode into the changes table. Only the dataset files, table names and snapshot date of the options are used,
+/// the changes being dated by the snapshot date or the current date.
+pub async fn diff(conn: &mut SqliteConnection, options: &ImportOptions) -> Result<(), ImportError> {
+    options.validate_table_names()?;
+    options.validate_snapshot_date()?;
+    crate::diff::run(conn, options).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::import;
+    use crate::{
+        dataset::{Dataset, Table},
+        error::ImportError,
+        fixtures::{count, memory_database, rows, Datasets},
+        options::ErrorPolicy,
+        report::ImportStatus,
+        SkipReason,
+    };
+
+    fn write_datasets(datasets: &Datasets, joined: bool) {
+        let (genres, professions, titles, writers, characters, types) = if joined {
+            (
+                "Drama,Short",
+                "actor",
+                "tt0000001",
+                "nm0000001",
+                "[\"Himself\"]",
+                "imdbDisplay",
+            )
+        } else {
+            ("\\N", "\\N", "\\N", "\\N", "\\N", "\\N")
+        };
+
+        datasets.write(
+            Dataset::TitleBasics,
+            &[&format!(
+                "tt0000001\tshort\tCarmencita\tCarmencita\t0\t1894\t\\N\t1\t{genres}"
+            )],
+        );
+        datasets.write(
+            Dataset::NameBasics,
+            &[&format!(
+                "nm0000001\tFred Astaire\t1899\t1987\t{professions}\t{titles}"
+            )],
+        );
+        datasets.write(
+            Dataset::TitleAkas,
+            &[&format!(
+                "tt0000001\t1\tCarmencita\tUS\t\\N\t{types}\t\\N\t0"
+            )],
+        );
+        datasets.write(
+            Dataset::TitleCrew,
+            &[&format!("tt0000001\tnm0000001\t{writers}")],
+        );
+        datasets.write(
+            Dataset::TitlePrincipals,
+            &[&format!("tt0000001\t1\tnm0000001\tself\t\\N\t{characters}")],
+        );
+    }
+
+    #[tokio::test]
+    async fn update_deletes_rows_of_emptied_lines() {
+        let datasets = Datasets::new("update-emptied");
+        let mut conn = memory_database().await;
+        let tables = [
+            Table::Title,
+            Table::Name,
+            Table::NameProfession,
+            Table::NameTitle,
+            Table::TitleGenre,
+            Table::TitleAka,
+            Table::TitleDirector,
+            Table::TitleWriter,
+            Table::TitleCharacter,
+        ];
+
+        write_datasets(&datasets, true);
+        let report = import(&mut conn, datasets.options().tables(tables))
+            .await
+            .unwrap();
+        assert_eq!(report.status, ImportStatus::Ok);
+        assert_eq!(count(&mut conn, "title_genre").await, 2);
+
+        write_datasets(&datasets, false);
+        let options = datasets.options().tables(tables).update(true);
+        let report = import(&mut conn, options).await.unwrap();
+        assert_eq!(report.status, ImportStatus::Ok);
+
+        for table_name in [
+            "title_genre",
+            "name_profession",
+            "name_title",
+            "title_writer",
+            "title_character",
+            "title_aka_type",
+        ] {
+            assert_eq!(count(&mut conn, table_name).await, 0, "{table_name}");
+        }
+        assert_eq!(count(&mut conn, "title_director").await, 1);
+        assert_eq!(
+            rows(&mut conn, "SELECT title FROM title_aka").await,
+            vec!["Carmencita"]
+        );
+    }
+
+    #[tokio::test]
+    async fn update_keeps_the_rows_of_malformed_lines() {
+        let datasets = Datasets::new("update-malformed");
+        let mut conn = memory_database().await;
+        let tables = [Table::Title, Table::TitleGenre];
+        let title = |id: u32, runtime: &str| {
+            format!("tt{id:07}\tshort\tCarmencita\tCarmencita\t0\t1894\t\\N\t{runtime}\tDrama")
+        };
+
+        datasets.write(
+            Dataset::TitleBasics,
+            &[&title(1, "1"), &title(2, "1"), &title(3, "1")],
+        );
+        import(&mut conn, datasets.options().tables(tables))
+            .await
+            .unwrap();
+
+        datasets.write(Dataset::TitleBasics, &[&title(1, "1"), &title(2, "abc")]);
+        let options = datasets.options().tables(tables).update(true);
+        let report = import(&mut conn, options).await.unwrap();
+        assert_eq!(report.status, ImportStatus::Ok);
+        assert_eq!(
+            rows(&mut conn, "SELECT id || '' FROM title").await,
+            vec!["1", "2"]
+        );
+        assert_eq!(
+            rows(&mut conn, "SELECT title_id || '' FROM title_genre").await,
+            vec!["1", "2"]
+        );
+
+        // Without the id of the malformed line, no row can be told to be missing from the dataset.
+        datasets.write(Dataset::TitleBasics, &[&title(1, "1"), "\\N\tshort"]);
+        let options = datasets.options().tables(tables).update(true);
+        import(&mut conn, options).await.unwrap();
+        assert_eq!(
+            rows(&mut conn, "SELECT id || '' FROM title").await,
+            vec!["1", "2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn update_requires_the_imdb_ids_of_the_table() {
+        let datasets = Datasets::new("update-imdb-ids");
+        let mut conn = memory_database().await;
+        write_datasets(&datasets, true);
+
+        let options = datasets.options().table(Table::Title).imdb_ids(true);
+        import(&mut conn, options).await.unwrap();
+
+        let options = datasets.options().table(Table::Title).update(true);
+        let report = import(&mut conn, options).await.unwrap();
+        assert_eq!(report.status, ImportStatus::Failed);
+        assert!(matches!(report.error(), Some(ImportError::Schema(_))));
+    }
+
+    #[tokio::test]
+    async fn prefixes_the_imdbsql_tables() {
+        let datasets = Datasets::new("prefix");
+        let mut conn = memory_database().await;
+        write_datasets(&datasets, true);
+
+        let options = datasets
+            .options()
+            .table(Table::Title)
+            .table_prefix("imdb_")
+            .on_error(ErrorPolicy::Quarantine);
+        import(&mut conn, options).await.unwrap();
+        super::diff(&mut conn, &datasets.options().table_prefix("imdb_"))
+            .await
+            .unwrap();
+
+        let tables = rows(
+            &mut conn,
+            "SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name",
+        )
+        .await;
+        assert_eq!(
+            tables,
+            vec![
+                "imdb__imdbsql_meta",
+                "imdb__imdbsql_version",
+                "imdb__rejects",
+                "imdb_changes",
+                "imdb_title",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn update_deletes_rows_of_vanished_lines() {
+        let datasets = Datasets::new("update-vanished");
+        let mut conn = memory_database().await;
+        let tables = [
+            Table::Title,
+            Table::Name,
+            Table::TitleRating,
+            Table::TitleAka,
+            Table::TitleEpisode,
+            Table::TitleDirector,
+            Table::TitleJob,
+            Table::TitleCharacter,
+        ];
+        let title =
+            |id: u32| format!("tt{id:07}\tshort\tCarmencita\tCarmencita\t0\t1894\t\\N\t1\tDrama");
+        let write_lines = |ids: &[u32]| {
+            let lines =
+                |line: &dyn Fn(u32) -> String| ids.iter().map(|&id| line(id)).collect::<Vec<_>>();
+            let write = |dataset, lines: Vec<String>| {
+                datasets.write(
+                    dataset,
+                    &lines.iter().map(String::as_str).collect::<Vec<_>>(),
+                )
+            };
+            write(
+                Dataset::TitleRatings,
+                lines(&|id| format!("tt{id:07}\t5.0\t10")),
+            );
+            write(
+                Dataset::TitleAkas,
+                lines(&|id| format!("tt{id:07}\t1\tCarmencita\tUS\t\\N\timdbDisplay\t\\N\t0")),
+            );
+            write(
+                Dataset::TitleEpisode,
+                lines(&|id| format!("tt{id:07}\ttt0000003\t1\t{id}")),
+            );
+            write(
+                Dataset::TitleCrew,
+                lines(&|id| format!("tt{id:07}\tnm0000001\t\\N")),
+            );
+            write(
+                Dataset::TitlePrincipals,
+                lines(&|id| format!("tt{id:07}\t1\tnm0000001\tself\t\\N\t[\"Herself\"]")),
+            );
+        };
+
+        datasets.write(Dataset::TitleBasics, &[&title(1), &title(2), &title(3)]);
+        datasets.write(
+            Dataset::NameBasics,
+            &["nm0000001\tFred Astaire\t1899\t1987\t\\N\t\\N"],
+        );
+        write_lines(&[1, 2]);
+        let report = import(&mut conn, datasets.options().tables(tables))
+            .await
+            .unwrap();
+        assert_eq!(report.status, ImportStatus::Ok);
+
+        // Title 2 has no line left in the joining datasets, title 1 a malformed rating.
+        write_lines(&[1]);
+        datasets.write(Dataset::TitleRatings, &["tt0000001\tgood\t10"]);
+        let options = datasets.options().tables(tables).update(true);
+        let report = import(&mut conn, options).await.unwrap();
+        assert_eq!(report.status, ImportStatus::Ok);
+
+        assert_eq!(count(&mut conn, "title").await, 3);
+        assert_eq!(
+            rows(&mut conn, "SELECT title_id || '' FROM title_rating").await,
+            vec!["1"]
+        );
+        for (table_name, key) in [
+            ("title_aka", "title_id"),
+            ("title_aka_type", "title_id"),
+            ("title_episode", "title_episode_id"),
+            ("title_director", "title_id"),
+            ("title_job", "title_id"),
+            ("title_character", "title_id"),
+        ] {
+            assert_eq!(
+                rows(&mut conn, &format!("SELECT {key} || '' FROM {table_name}")).await,
+                vec!["1"],
+                "{table_name}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn update_keeps_the_rows_of_other_tables() {
+        let datasets = Datasets::new("update-other-tables");
+        let mut conn = memory_database().await;
+        let title =
+            |id: u32| format!("tt{id:07}\tshort\tCarmencita\tCarmencita\t0\t1894\t\\N\t1\tDrama");
+
+        datasets.write(Dataset::TitleBasics, &[&title(1), &title(2)]);
+        import(&mut conn, datasets.options().table(Table::Title))
+            .await
+            .unwrap();
+        sqlx::raw_sql(
+            "CREATE TABLE watchlist (title_id integer not null references title(id)); INSERT INTO watchlist VALUES (2)",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        // Deleting the vanished title fails on the foreign key of the application table, rather than emptying it.
+        datasets.write(Dataset::TitleBasics, &[&title(1)]);
+        let options = datasets.options().table(Table::Title).update(true);
+        let report = import(&mut conn, options).await.unwrap();
+        assert_eq!(report.status, ImportStatus::Failed);
+        assert_eq!(count(&mut conn, "title").await, 2);
+        assert_eq!(count(&mut conn, "watchlist").await, 1);
+    }
+
+    #[tokio::test]
+    async fn records_the_given_arguments() {
+        let datasets = Datasets::new("arguments");
+        let mut conn = memory_database().await;
+        write_datasets(&datasets, true);
+
+        let options = datasets
+            .options()
+            .table(Table::Title)
+            .arguments(["imdb.db", "--title"]);
+        import(&mut conn, options).await.unwrap();
+        assert_eq!(
+            rows(&mut conn, "SELECT arguments FROM _imdbsql_meta").await,
+            vec!["imdb.db --title"]
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_invalid_snapshot_dates() {
+        let datasets = Datasets::new("snapshot-date");
+        let mut conn = memory_database().await;
+
+        let options = datasets
+            .options()
+            .table(Table::TitleRating)
+            .snapshot_ratings(true)
+            .snapshot_date("2024-02-30");
+        let result = import(&mut conn, options).await;
+        assert!(matches!(result, Err(ImportError::Options(_))));
+
+        let options = datasets.options().snapshot_date("01/02/2024");
+        let error = super::diff(&mut conn, &options).await.unwrap_err();
+        assert!(matches!(error, ImportError::Options(_)), "{error}");
+    }
+
+    #[tokio::test]
+    async fn refuses_conflicting_table_names() {
+        let datasets = Datasets::new("conflicting-names");
+        let mut conn = memory_database().await;
+
+        for options in [
+            datasets.options().table_name(Table::Title, "name"),
+            datasets
+                .options()
+                .table_name(Table::Title, "movie")
+                .table_name(Table::Name, "Movie"),
+            datasets
+                .options()
+                .table_name(Table::TitleGenre, "title_rating_history"),
+            datasets
+                .options()
+                .table_name(Table::TitleJob, "title_aka_type"),
+        ] {
+            let result = import(&mut conn, options.table(Table::Title)).await;
+            assert!(matches!(result, Err(ImportError::Options(_))));
+        }
+
+        let options = datasets
+            .options()
+            .table_name(Table::Title, "movie")
+            .table_name(Table::Title, "film");
+        assert!(options.validate_table_names().is_ok());
+    }
+
+    #[tokio::test]
+    async fn imports_inside_spawned_tasks() {
+        let datasets = Datasets::new("spawned");
+        write_datasets(&datasets, true);
+
+        let options = datasets.options().table(Table::Title);
+        let diff_options = datasets.options();
+        let status = tokio::spawn(async move {
+            let mut conn = memory_database().await;
+            let status = import(&mut conn, options).await.unwrap().status;
+            super::diff(&mut conn, &diff_options).await.unwrap();
+            status
+        })
+        .await
+        .unwrap();
+        assert_eq!(status, ImportStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn restores_the_pragmas_of_failed_imports() {
+        let datasets = Datasets::new("failed-pragmas");
+        let mut conn = memory_database().await;
+
+        // Collecting the adult titles aborts on the malformed runtime, once the import profile is applied.
+        datasets.write(
+            Dataset::TitleBasics,
+            &["tt0000001\tshort\tCarmencita\tCarmencita\t1\t1894\t\\N\tlong\tDrama"],
+        );
+        let options = datasets
+            .options()
+            .table(Table::Title)
+            .fast(true)
+            .exclude_adult(true)
+            .on_error(ErrorPolicy::Abort);
+        let result = import(&mut conn, options).await;
+        assert!(matches!(result, Err(ImportError::Parse { .. })));
+
+        let synchronous: i64 = sqlx::query_scalar("PRAGMA synchronous")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(synchronous, 2);
+        assert_eq!(rows(&mut conn, "PRAGMA locking_mode").await, vec!["normal"]);
+    }
+
+    #[tokio::test]
+    async fn excludes_adult_titles() {
+        let datasets = Datasets::new("exclude-adult");
+        let mut conn = memory_database().await;
+        let title = |id: u32, adult: u8| {
+            format!("tt{id:07}\tshort\tCarmencita\tCarmencita\t{adult}\t1894\t\\N\t1\tDrama")
+        };
+
+        // The malformed line is skipped by the policy, collecting the adult titles included.
+        datasets.write(
+            Dataset::TitleBasics,
+            &[
+                &title(1, 0),
+                &title(2, 1),
+                &title(3, 1),
+                &title(4, 0),
+                "tt0000005\tshort",
+            ],
+        );
+        datasets.write(
+            Dataset::NameBasics,
+            &["nm0000001\tFred Astaire\t1899\t1987\tactor\ttt0000001,tt0000002"],
+        );
+        datasets.write(
+            Dataset::TitleAkas,
+            &[
+                "tt0000001\t1\tCarmencita\tUS\t\\N\t\\N\t\\N\t0",
+                "tt0000002\t1\tCarmencita\tUS\t\\N\t\\N\t\\N\t0",
+            ],
+        );
+        datasets.write(
+            Dataset::TitleEpisode,
+            &["tt0000002\ttt0000001\t1\t1", "tt0000004\ttt0000003\t1\t1"],
+        );
+
+        let options = datasets
+            .options()
+            .tables([
+                Table::Title,
+                Table::TitleGenre,
+                Table::Name,
+                Table::NameTitle,
+                Table::TitleAka,
+                Table::TitleEpisode,
+            ])
+            .exclude_adult(true)
+            .on_error(ErrorPolicy::Skip);
+        let report = import(&mut conn, options).await.unwrap();
+        assert_eq!(report.status, ImportStatus::Ok);
+
+        assert_eq!(
+            rows(&mut conn, "SELECT id || '' FROM title ORDER BY id").await,
+            vec!["1", "4"]
+        );
+        assert_eq!(
+            rows(
+                &mut conn,
+                "SELECT title_id || '' FROM title_genre ORDER BY title_id"
+            )
+            .await,
+            vec!["1", "4"]
+        );
+        assert_eq!(
+            rows(&mut conn, "SELECT title_id || '' FROM name_title").await,
+            vec!["1"]
+        );
+        assert_eq!(
+            rows(&mut conn, "SELECT title_id || '' FROM title_aka").await,
+            vec!["1"]
+        );
+        assert_eq!(count(&mut conn, "title_episode").await, 0);
+
+        // The adult rows are left out, rather than refused for missing their title.
+        for table in &report.tables {
+            assert_eq!(
+                table.skipped_for(SkipReason::MissingForeignKey),
+                0,
+                "{}",
+                table.table_name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn excluding_adult_titles_follows_the_error_policy() {
+        let datasets = Datasets::new("exclude-adult-policy");
+        datasets.write(
+            Dataset::TitleBasics,
+            &[
+                "tt0000001\tshort\tCarmencita\tCarmencita\t1\t1894\t\\N\t1\tDrama",
+                "tt0000002\tshort",
+            ],
+        );
+        let options = || datasets.options().table(Table::Title).exclude_adult(true);
+
+        let mut conn = memory_database().await;
+        let result = import(&mut conn, options().on_error(ErrorPolicy::Abort)).await;
+        assert!(matches!(result, Err(ImportError::Parse { line: 3, .. })));
+
+        // The line is only quarantined once, by the title table rather than by collecting the adult titles.
+        let mut conn = memory_database().await;
+        let report = import(&mut conn, options().on_error(ErrorPolicy::Quarantine))
+            .await
+            .unwrap();
+        assert_eq!(report.status, ImportStatus::Ok);
+        assert_eq!(count(&mut conn, "title").await, 0);
+        assert_eq!(
+            rows(
+                &mut conn,
+                "SELECT line_number || ' ' || table_name FROM _rejects"
+            )
+            .await,
+            vec!["3 title"]
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use crate::{dataset::Table, error::ImportError, options::TableNames, utils::execute_sql};
+use sqlx::SqliteConnection;
+
+/// Indexed columns of each table, covering every foreign key column along with the searchable names.
+/// Primary keys starting with title_id already cover it, so Title_Aka only needs indexes on its child tables
+/// and Title_Job and Title_Character only on their name_id.
+fn indexes(table: Table, name: String) -> Vec<(String, &'static str)> {
+    match table {
+        Table::Name => vec![(name, "name")],
+        Table::Title => vec![(name, "primary_name")],
+        Table::NameProfession => vec![(name, "name_id")],
+        Table::TitleGenre | Table::TitleRating => vec![(name, "title_id")],
+        Table::NameTitle => vec![(name.clone(), "name_id"), (name, "title_id")],
+        Table::TitleDirector | Table::TitleWriter => {
+            vec![(name.clone(), "title_id"), (name, "name_id")]
+        }
+        Table::TitleJob | Table::TitleCharacter => vec![(name, "name_id")],
+        Table::TitleEpisode => vec![
+            (name.clone(), "title_episode_id"),
+            (name, "title_series_id"),
+        ],
+        Table::TitleAka => vec![
+            (format!("{name}_type"), "title_id, ordering"),
+            (format!("{name}_attribute"), "title_id, ordering"),
+        ],
+    }
+}
+
+/// Creates the indexes of the table, done once the rows are inserted as maintaining them during
+/// the insertion slows it down considerably.
+pub async fn create_indexes(
+    table: Table,
+    names: &TableNames,
+    conn: &mut SqliteConnection,
+) -> Result<(), ImportError> {
+    let name = names.get(table);
+    for (table_name, columns) in indexes(table, name) {
+        let index_name = format!("{table_name}_{}_index", columns.replace(", ", "_"));
+        execute_sql(
+            format!("CREATE INDEX IF NOT EXISTS {index_name} ON {table_name} ({columns})").as_str(),
+            &mut *conn,
+        )
+        .await
+        .map_err(|e| ImportError::database(format!("Unable to create {index_name} index"), e))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+//! Converts the IMDb datasets into SQLite tables.
+//!
+//! ```no_run
+//! use imdbsql::{ImportOptions, ImportStatus, Table};
+//!
+//! # async fn run() -> Result<(), imdbsql::ImportError> {
+//! let mut conn = imdbsql::connect("imdb.db").await?;
+//! let options = ImportOptions::new()
+//!     .tables([Table::Title, Table::Name, Table::NameTitle])
+//!     .input_dir("datasets");
+//! let report = imdbsql::import(&mut conn, options).await?;
+//! if report.status != ImportStatus::Ok {
+//!     eprintln!("Failed to import {}", report.failed_tables().join(", "));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The dataset files can also be read without a database, through the readers yielding the typed records
+//! of every line, such as `TitleBasicsReader` and `TitlePrincipalsReader`.
+
+mod dataset;
+mod diff;
+mod error;
+#[cfg(test)]
+mod fixtures;
+mod import;
+mod indexes;
+mod meta;
+mod migrations;
+mod options;
+mod parsers;
+mod profile;
+mod readers;
+mod records;
+mod report;
+mod tsv;
+mod update;
+mod utils;
+mod views;
+
+pub use dataset::{Dataset, Table};
+pub use error::{ImportError, ParseError};
+pub use import::{connect, diff, import};
+pub use options::{ErrorPolicy, ImportOptions};
+pub use parsers::rejects::SkipReason;
+pub use readers::{
+    DatasetReader, NameBasicsReader, TitleAkasReader, TitleBasicsReader, TitleCrewReader,
+    TitleEpisodeReader, TitlePrincipalsReader, TitleRatingsReader,
+};
+pub use records::{
+    NameBasics, Record, TitleAka, TitleBasics, TitleCrew, TitleEpisode, TitlePrincipal,
+    TitleRating,
+};
+pub use report::{ImportReport, ImportStatus, TableReport};
+pub use tsv::DatasetHeader;
@@ -0,0 +1,108 @@
+use crate::{
+    error::ImportError,
+    options::ImportOptions,
+    report::{ImportStatus, TableReport},
+    utils::{execute_sql, table_exists, timestamp},
+};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use sqlx::SqliteConnection;
+use std::{
+    fs::{self, File},
+    io,
+    time::SystemTime,
+};
+
+/// Table every import is recorded into, so the origin of a database can be verified.
+pub const META_TABLE: &str = "_imdbsql_meta";
+
+/// Records an import as a row of the metadata table. The tables, datasets and row counts are stored as JSON,
+/// so they can be queried with the SQLite json functions.
+pub async fn record_import(
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+    started_at: SystemTime,
+    datasets: &[String],
+    reports: &[TableReport],
+    status: ImportStatus,
+) -> Result<(), ImportError> {
+    let meta_table = options.table_names.internal(META_TABLE);
+    execute_sql(format!("CREATE TABLE IF NOT EXISTS {meta_table} (id integer primary key, version text not null, arguments text not null, status text not null, tables text not null, datasets text not null, row_counts text not null, started_at text not null, finished_at text not null)").as_str(), &mut *conn)
+        .await
+        .map_err(|e| ImportError::database(format!("Unable to create {meta_table} table"), e))?;
+
+    options.print_step(format_args!("-- Recording the import into {meta_table} --"));
+    let mut tables: Vec<&str> = Vec::new();
+    for report in reports {
+        if !tables.contains(&report.table.name()) {
+            tables.push(report.table.name());
+        }
+    }
+
+    let datasets = datasets
+        .iter()
+        .map(|file_name| describe_dataset(file_name))
+        .collect::<Result<Vec<Value>, ImportError>>()?;
+
+    let mut row_counts = Map::new();
+    for report in reports {
+        let table_name = &report.table_name;
+        if row_counts.contains_key(table_name) || !table_exists(table_name, conn).await? {
+            continue;
+        }
+
+        let (count,): (i64,) = sqlx::query_as(&format!("SELECT count(*) FROM {table_name}"))
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| {
+                ImportError::database(format!("Unable to count the rows of {table_name}"), e)
+            })?;
+        row_counts.insert(table_name.clone(), count.into());
+    }
+
+    sqlx::query(&format!("INSERT INTO {meta_table} (version, arguments, status, tables, datasets, row_counts, started_at, finished_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"))
+        .bind(env!("CARGO_PKG_VERSION"))
+        .bind(options.arguments.join(" "))
+        .bind(status.name())
+        .bind(json!(tables).to_string())
+        .bind(Value::Array(datasets).to_string())
+        .bind(Value::Object(row_counts).to_string())
+        .bind(timestamp(started_at))
+        .bind(timestamp(SystemTime::now()))
+        .execute(conn)
+        .await
+        .map_err(|e| ImportError::database(format!("Unable to record the import into {meta_table}"), e))?;
+
+    Ok(())
+}
+
+/// Name, size, modification time and SHA-256 checksum of a dataset file, as read from the disk.
+fn describe_dataset(file_name: &str) -> Result<Value, ImportError> {
+    let metadata = fs::metadata(file_name)
+        .map_err(|e| ImportError::io(format!("Unable to read metadata of {file_name}"), e))?;
+    let modified = metadata.modified().map_err(|e| {
+        ImportError::io(
+            format!("Unable to read modification date of {file_name}"),
+            e,
+        )
+    })?;
+
+    let mut hasher = Sha256::new();
+    File::open(file_name)
+        .and_then(|mut file| io::copy(&mut file, &mut hasher))
+        .map_err(|e| {
+            ImportError::io(format!("Unable to compute the checksum of {file_name}"), e)
+        })?;
+    let checksum: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+
+    Ok(json!({
+        "file_name": file_name,
+        "size": metadata.len(),
+        "modified": timestamp(modified),
+        "sha256": checksum,
+    }))
+}
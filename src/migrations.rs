@@ -0,0 +1,370 @@
+use crate::{
+    dataset::Table,
+    error::ImportError,
+    options::TableNames,
+    utils::{execute_sql, has_column, print_line, table_exists},
+};
+use sqlx::{Connection, SqliteConnection};
+
+/// Version of the schema created by this release, stored in the `user_version` of the database. When the tables are
+/// prefixed or renamed, the database may belong to another application, so the version is stored in the
+/// `VERSION_TABLE` of the prefix instead.
+///
+/// 1. title stores is_adult and runtime_minutes.
+/// 2. title_job and title_character store the principal ordering, as part of their primary keys.
+pub const SCHEMA_VERSION: i64 = 2;
+
+/// Table holding the schema version of the tables sharing a prefix.
+pub const VERSION_TABLE: &str = "_imdbsql_version";
+
+/// Upgrades the schema of an existing database to `SCHEMA_VERSION`, one version at a time.
+/// Databases created before versioning are at version 0, their tables being migrated according to their columns.
+/// Tables that are about to be overwritten are dropped rather than migrated. A migration that can only be completed
+/// by overwriting tables that are not selected is left for a later import, the database staying at the version
+/// before it.
+pub async fn migrate(
+    conn: &mut SqliteConnection,
+    selected: &[Table],
+    overwritten: &[Table],
+    names: &TableNames,
+    progress: bool,
+) -> Result<(), ImportError> {
+    let version = check_version(conn, names).await?;
+    for version in version + 1..=SCHEMA_VERSION {
+        let mut tx = conn
+            .begin()
+            .await
+            .map_err(|e| ImportError::database("Failed to start transaction", e))?;
+
+        let migrated = match version {
+            1 => add_title_flags(&mut tx, names, progress).await?,
+            2 => add_principal_ordering(&mut tx, selected, overwritten, names, progress).await?,
+            _ => unreachable!("no migration to schema version {version}"),
+        };
+
+        if migrated {
+            write_version(version, &mut tx, names).await?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| ImportError::database("Failed to commit transactions", e))?;
+
+        if !migrated {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the schema version of the tables, refusing the tables created by a newer version.
+async fn check_version(
+    conn: &mut SqliteConnection,
+    names: &TableNames,
+) -> Result<i64, ImportError> {
+    let version = read_version(conn, names).await?;
+    if version > SCHEMA_VERSION {
+        let tables = if names.is_default() {
+            "The database has".to_string()
+        } else {
+            format!("The tables prefixed with \"{}\" have", names.prefix)
+        };
+        return Err(ImportError::Schema(format!(
+            "{tables} schema version {version} which is newer than version {SCHEMA_VERSION} supported by imdbsql {}, upgrade imdbsql to use it",
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+
+    Ok(version)
+}
+
+async fn read_version(conn: &mut SqliteConnection, names: &TableNames) -> Result<i64, ImportError> {
+    let query = if names.is_default() {
+        "PRAGMA user_version".to_string()
+    } else {
+        let version_table = names.internal(VERSION_TABLE);
+        if !table_exists(&version_table, conn).await? {
+            return Ok(0);
+        }
+
+        format!("SELECT coalesce(max(version), 0) FROM {version_table}")
+    };
+
+    sqlx::query_scalar(&query)
+        .fetch_one(conn)
+        .await
+        .map_err(|e| ImportError::database("Unable to read the schema version", e))
+}
+
+async fn write_version(
+    version: i64,
+    conn: &mut SqliteConnection,
+    names: &TableNames,
+) -> Result<(), ImportError> {
+    let query = if names.is_default() {
+        format!("PRAGMA user_version = {version}")
+    } else {
+        let version_table = names.internal(VERSION_TABLE);
+        format!("CREATE TABLE IF NOT EXISTS {version_table} (version integer not null); DELETE FROM {version_table}; INSERT INTO {version_table} VALUES ({version})")
+    };
+
+    execute_sql(query.as_str(), conn).await.map_err(|e| {
+        ImportError::database(format!("Unable to set the schema version to {version}"), e)
+    })?;
+
+    Ok(())
+}
+
+/// Checks that the tables compared by `diff` can be read without migrating the database, which is left untouched.
+pub async fn check_diffable(
+    conn: &mut SqliteConnection,
+    names: &TableNames,
+) -> Result<(), ImportError> {
+    let version = check_version(conn, names).await?;
+    let table_name = &names.get(Table::Title);
+    if version < 1
+        && has_column(table_name, "id", conn).await?
+        && !has_column(table_name, "is_adult", conn).await?
+    {
+        return Err(ImportError::Schema(format!(
+            "The {table_name} table was created by an older imdbsql, import into the database once to migrate it before comparing"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Adds is_adult and runtime_minutes to title. Their values are unknown until the titles are imported again,
+/// so every title is left as not adult with no runtime.
+async fn add_title_flags(
+    conn: &mut SqliteConnection,
+    names: &TableNames,
+    progress: bool,
+) -> Result<bool, ImportError> {
+    let table_name = &names.get(Table::Title);
+    if !has_column(table_name, "id", conn).await?
+        || has_column(table_name, "is_adult", conn).await?
+    {
+        return Ok(true);
+    }
+
+    execute_sql(
+        format!(
+            "ALTER TABLE {table_name} ADD COLUMN is_adult boolean not null default 0; ALTER TABLE {table_name} ADD COLUMN runtime_minutes integer"
+        )
+        .as_str(),
+        conn,
+    )
+    .await
+    .map_err(|e| ImportError::database(format!("Unable to migrate {table_name} table"), e))?;
+
+    if progress {
+        print_line(format_args!(
+            "-- Added is_adult and runtime_minutes to {table_name}, update the titles to fill them --"
+        ));
+    }
+    Ok(true)
+}
+
+/// The principal ordering can not be recovered from the rows stored without it, so title_job and title_character
+/// can only be parsed again. The import is refused when it selects them without overwriting them, and the migration
+/// is left incomplete when they are not selected.
+async fn add_principal_ordering(
+    conn: &mut SqliteConnection,
+    selected: &[Table],
+    overwritten: &[Table],
+    names: &TableNames,
+    progress: bool,
+) -> Result<bool, ImportError> {
+    let mut stale = Vec::new();
+    for table in [Table::TitleJob, Table::TitleCharacter] {
+        let table_name = &names.get(table);
+        if has_column(table_name, "title_id", conn).await?
+            && !has_column(table_name, "ordering", conn).await?
+        {
+            stale.push(table);
+        }
+    }
+
+    if stale.is_empty() {
+        return Ok(true);
+    }
+
+    let table_names: Vec<String> = stale.iter().map(|&table| names.get(table)).collect();
+    let flags: Vec<String> = stale
+        .iter()
+        .map(|table| format!("--{}", table.name()))
+        .collect();
+    let hint = if stale.len() > 1 {
+        format!(
+            "the {} tables were created without the principal ordering, overwrite them with --overwrite {}",
+            table_names.join(" and "),
+            flags.join(" ")
+        )
+    } else {
+        format!(
+            "the {} table was created without the principal ordering, overwrite it with --overwrite {}",
+            table_names[0], flags[0]
+        )
+    };
+    if stale
+        .iter()
+        .any(|table| selected.contains(table) && !overwritten.contains(table))
+    {
+        return Err(ImportError::Schema(format!(
+            "Unable to migrate the database, {hint}"
+        )));
+    }
+
+    for &table in stale.iter().filter(|table| overwritten.contains(table)) {
+        // Nothing references the principal tables, so they can be dropped with foreign keys enforced.
+        let table_name = &names.get(table);
+        execute_sql(format!("DROP TABLE {table_name}").as_str(), &mut *conn)
+            .await
+            .map_err(|e| ImportError::database(format!("Unable to drop {table_name} table"), e))?;
+    }
+
+    if stale.iter().all(|table| overwritten.contains(table)) {
+        return Ok(true);
+    }
+
+    if progress {
+        print_line(format_args!(
+            "-- Schema version {SCHEMA_VERSION} is left for later as {hint} --"
+        ));
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_diffable, migrate, SCHEMA_VERSION, VERSION_TABLE};
+    use crate::{
+        dataset::Table, error::ImportError, fixtures::memory_database, options::TableNames,
+        utils::has_column,
+    };
+    use sqlx::SqliteConnection;
+
+    /// Tables as created by the releases before the schema was versioned.
+    async fn unversioned_database(prefix: &str) -> SqliteConnection {
+        let mut conn = memory_database().await;
+        sqlx::raw_sql(&format!(
+            "CREATE TABLE {prefix}title (id integer primary key, primary_name text not null, original_name text not null, title_type text not null, release_date integer, end_date integer);
+            CREATE TABLE {prefix}title_job (title_id integer not null, name_id integer not null, category text not null, job text);
+            CREATE TABLE {prefix}title_character (title_id integer not null, name_id integer not null, character text not null)"
+        ))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        conn
+    }
+
+    async fn version(conn: &mut SqliteConnection) -> i64 {
+        sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn leaves_unselected_principal_tables() {
+        let mut conn = unversioned_database("").await;
+        let names = TableNames::default();
+
+        migrate(&mut conn, &[Table::TitleRating], &[], &names, false)
+            .await
+            .unwrap();
+        assert!(has_column("title", "is_adult", &mut conn).await.unwrap());
+        assert!(has_column("title_job", "title_id", &mut conn)
+            .await
+            .unwrap());
+        assert_eq!(version(&mut conn).await, 1);
+
+        let principals = [Table::TitleJob, Table::TitleCharacter];
+        migrate(&mut conn, &principals, &principals, &names, false)
+            .await
+            .unwrap();
+        assert!(!has_column("title_job", "title_id", &mut conn)
+            .await
+            .unwrap());
+        assert_eq!(version(&mut conn).await, SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn refuses_selected_principal_tables() {
+        let mut conn = unversioned_database("imdb_").await;
+        let names = TableNames {
+            prefix: "imdb_".to_string(),
+            renames: Vec::new(),
+        };
+
+        let error = migrate(&mut conn, &[Table::TitleJob], &[], &names, false)
+            .await
+            .unwrap_err();
+        let ImportError::Schema(message) = error else {
+            panic!("unexpected error {error}");
+        };
+        assert!(message.contains("imdb_title_job and imdb_title_character tables"));
+        assert!(message.ends_with("--overwrite --title_job --title_character"));
+    }
+
+    #[tokio::test]
+    async fn checks_diff_without_migrating() {
+        let mut conn = unversioned_database("").await;
+        let names = TableNames::default();
+
+        let error = check_diffable(&mut conn, &names).await.unwrap_err();
+        assert!(matches!(error, ImportError::Schema(_)));
+        assert!(!has_column("title", "is_adult", &mut conn).await.unwrap());
+        assert_eq!(version(&mut conn).await, 0);
+
+        migrate(&mut conn, &[Table::Title], &[], &names, false)
+            .await
+            .unwrap();
+        check_diffable(&mut conn, &names).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_newer_versions() {
+        let mut conn = memory_database().await;
+        sqlx::raw_sql("PRAGMA user_version = 9")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let error = migrate(
+            &mut conn,
+            &[Table::Title],
+            &[],
+            &TableNames::default(),
+            false,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, ImportError::Schema(_)));
+    }
+
+    #[tokio::test]
+    async fn keeps_the_user_version_of_prefixed_tables() {
+        let mut conn = memory_database().await;
+        sqlx::raw_sql("PRAGMA user_version = 7")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        let names = TableNames {
+            prefix: "imdb_".to_string(),
+            renames: Vec::new(),
+        };
+
+        migrate(&mut conn, &[Table::Title], &[], &names, false)
+            .await
+            .unwrap();
+        assert_eq!(version(&mut conn).await, 7);
+        let stored: i64 = sqlx::query_scalar(&format!("SELECT version FROM imdb_{VERSION_TABLE}"))
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(stored, SCHEMA_VERSION);
+    }
+}
@@ -0,0 +1,412 @@
+use crate::{
+    dataset::{Dataset, Table},
+    error::ImportError,
+    utils::{file_date, is_date, print_line, resolve_dataset},
+};
+use std::{
+    fmt::Display,
+    io::{self, Write},
+    path::Path,
+};
+
+/// What happens to lines that fail to parse and rows that fail to insert, including the rows whose foreign keys are not found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorPolicy {
+    /// Stop parsing the dataset and discard what was inserted from it
+    Abort,
+    /// Count and drop them
+    Skip,
+    /// Count and drop them, writing the offending lines to the _rejects table
+    Quarantine,
+}
+
+/// Names the tables are given in the database.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TableNames {
+    pub prefix: String,
+    pub renames: Vec<(Table, String)>,
+}
+
+impl TableNames {
+    /// Name of the table in the database.
+    pub fn get(&self, table: Table) -> String {
+        match self.renames.iter().rev().find(|(t, _)| *t == table) {
+            Some((_, name)) => name.clone(),
+            None => format!("{}{}", self.prefix, table.name()),
+        }
+    }
+
+    /// Names of every table imdbsql creates for the dataset tables, including the aka types and attributes
+    /// and the ratings history.
+    pub fn dataset_tables(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for table in Table::ALL {
+            let name = self.get(table);
+            match table {
+                Table::TitleAka => {
+                    names.push(format!("{name}_type"));
+                    names.push(format!("{name}_attribute"));
+                }
+                Table::TitleRating => names.push(format!("{name}_history")),
+                _ => {}
+            }
+            names.push(name);
+        }
+
+        names
+    }
+
+    /// Name of a table imdbsql keeps next to the dataset tables, such as changes or _rejects, with the prefix
+    /// prepended so several imports can share a database.
+    pub fn internal(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+
+    /// Whether the tables keep the names of the datasets, the database then being assumed to belong to imdbsql.
+    pub fn is_default(&self) -> bool {
+        self.prefix.is_empty() && self.renames.is_empty()
+    }
+}
+
+/// Options of an import, built by chaining the setters onto `ImportOptions::new`.
+///
+/// ```no_run
+/// use imdbsql::{ImportOptions, Table};
+///
+/// let options = ImportOptions::new()
+///     .tables([Table::Title, Table::Name, Table::NameTitle])
+///     .input_dir("datasets")
+///     .table_prefix("imdb_");
+/// ```
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    pub(crate) tables: Vec<Table>,
+    pub(crate) input_dir: String,
+    pub(crate) files: Vec<(Dataset, String)>,
+    pub(crate) table_names: TableNames,
+    pub(crate) batch_size: usize,
+    pub(crate) fast: bool,
+    pub(crate) safe: bool,
+    pub(crate) vacuum: bool,
+    pub(crate) indexes: bool,
+    pub(crate) overwrite: bool,
+    pub(crate) imdb_ids: bool,
+    pub(crate) update: bool,
+    pub(crate) snapshot_ratings: bool,
+    pub(crate) snapshot_date: Option<String>,
+    pub(crate) on_error: ErrorPolicy,
+    pub(crate) exclude_adult: bool,
+    pub(crate) log: bool,
+    pub(crate) progress: bool,
+    pub(crate) arguments: Vec<String>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            tables: Vec::new(),
+            input_dir: ".".to_string(),
+            files: Vec::new(),
+            table_names: TableNames::default(),
+            batch_size: 1000,
+            fast: false,
+            safe: false,
+            vacuum: false,
+            indexes: true,
+            overwrite: false,
+            imdb_ids: false,
+            update: false,
+            snapshot_ratings: false,
+            snapshot_date: None,
+            on_error: ErrorPolicy::Skip,
+            exclude_adult: false,
+            log: false,
+            progress: false,
+            arguments: Vec::new(),
+        }
+    }
+}
+
+impl ImportOptions {
+    /// Options importing no table, with the defaults of the command line.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a table to import, the tables are always imported in the order of `Table::ALL`.
+    pub fn table(mut self, table: Table) -> Self {
+        if !self.tables.contains(&table) {
+            self.tables.push(table);
+        }
+
+        self
+    }
+
+    /// Adds tables to import.
+    pub fn tables(self, tables: impl IntoIterator<Item = Table>) -> Self {
+        tables.into_iter().fold(self, Self::table)
+    }
+
+    /// Directory containing the dataset files, the current directory by default.
+    pub fn input_dir(mut self, dir: impl Into<String>) -> Self {
+        self.input_dir = dir.into();
+        self
+    }
+
+    /// Path of a dataset file, overriding the file found in the input directory.
+    pub fn dataset_file(mut self, dataset: Dataset, path: impl Into<String>) -> Self {
+        self.files.retain(|(d, _)| *d != dataset);
+        self.files.push((dataset, path.into()));
+        self
+    }
+
+    /// Prepends the prefix to the name of every table, along with the tables derived from them, their indexes and views,
+    /// and the changes, _rejects and _imdbsql_meta tables.
+    pub fn table_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.table_names.prefix = prefix.into();
+        self
+    }
+
+    /// Gives the table another name, the prefix is not prepended to it.
+    pub fn table_name(mut self, table: Table, name: impl Into<String>) -> Self {
+        self.table_names.renames.push((table, name.into()));
+        self
+    }
+
+    /// How many rows are inserted together in a single statement.
+    pub fn batch_size(mut self, rows: usize) -> Self {
+        self.batch_size = rows;
+        self
+    }
+
+    /// Tunes SQLite for a one-shot bulk import, a crash during the import can corrupt the database.
+    pub fn fast(mut self, fast: bool) -> Self {
+        self.fast = fast;
+        self
+    }
+
+    /// Imports through a write-ahead log, keeping the database consistent if the import is interrupted.
+    pub fn safe(mut self, safe: bool) -> Self {
+        self.safe = safe;
+        self
+    }
+
+    /// Rebuilds the database file once the import is done.
+    pub fn vacuum(mut self, vacuum: bool) -> Self {
+        self.vacuum = vacuum;
+        self
+    }
+
+    /// Creates the indexes on the foreign key and name columns once the tables are parsed, enabled by default.
+    pub fn indexes(mut self, indexes: bool) -> Self {
+        self.indexes = indexes;
+        self
+    }
+
+    /// Drops the imported tables before inserting into them.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Keeps the tconst and nconst identifiers and creates views exposing them next to every joining table.
+    pub fn imdb_ids(mut self, imdb_ids: bool) -> Self {
+        self.imdb_ids = imdb_ids;
+        self
+    }
+
+    /// Upserts the dataset into the existing tables, deleting the rows missing from it.
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// Appends the imported ratings to the title_rating_history table.
+    pub fn snapshot_ratings(mut self, snapshot_ratings: bool) -> Self {
+        self.snapshot_ratings = snapshot_ratings;
+        self
+    }
+
+    /// Date (YYYY-MM-DD) of the ratings snapshot, the modification date of title.ratings.tsv by default.
+    pub fn snapshot_date(mut self, date: impl Into<String>) -> Self {
+        self.snapshot_date = Some(date.into());
+        self
+    }
+
+    /// What happens to the lines failing to parse and the rows failing to insert, skipped by default.
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.on_error = policy;
+        self
+    }
+
+    /// Skips the titles flagged as adult, along with every row referencing them.
+    pub fn exclude_adult(mut self, exclude_adult: bool) -> Self {
+        self.exclude_adult = exclude_adult;
+        self
+    }
+
+    /// Prints the rows failing to insert.
+    pub fn log(mut self, log: bool) -> Self {
+        self.log = log;
+        self
+    }
+
+    /// Prints the steps of the import and the progress of every dataset to stdout, and the errors of the failed tables
+    /// to stderr as they happen. Off by default, the command line turns it on unless --quiet is given.
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Arguments the import was started with, recorded into the _imdbsql_meta table. The command line passes its own.
+    pub fn arguments(mut self, arguments: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.arguments = arguments.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Prints a step of the import when the progress is shown.
+    pub(crate) fn print_step(&self, step: impl Display) {
+        if self.progress {
+            print_line(step);
+        }
+    }
+
+    /// Prints the error of a table failing without failing the import, when the progress is shown.
+    pub(crate) fn print_error(&self, error: &ImportError) {
+        if self.progress {
+            let _ = writeln!(io::stderr().lock(), "\n{error}");
+        }
+    }
+
+    /// Tables to import, in the order they should be parsed.
+    pub(crate) fn selected_tables(&self) -> Vec<Table> {
+        Table::ALL
+            .into_iter()
+            .filter(|table| self.tables.contains(table))
+            .collect()
+    }
+
+    /// Path of the dataset file, either the explicit override or the file inside the input directory.
+    pub(crate) fn dataset_path(&self, dataset: Dataset) -> String {
+        match self.files.iter().find(|(d, _)| *d == dataset) {
+            Some((_, path)) => path.clone(),
+            None => {
+                let path = Path::new(&self.input_dir).join(dataset.file_name());
+                resolve_dataset(&path.to_string_lossy())
+            }
+        }
+    }
+
+    /// Date of the ratings snapshot, resolved by `resolve_snapshot_date`, only set when ratings are snapshot.
+    pub(crate) fn ratings_snapshot_date(&self) -> Option<&str> {
+        self.snapshot_date
+            .as_deref()
+            .filter(|_| self.snapshot_ratings)
+    }
+
+    /// Falls back to the modification date of title.ratings.tsv when no snapshot date was given.
+    pub(crate) fn resolve_snapshot_date(&mut self) -> Result<(), ImportError> {
+        if self.snapshot_ratings && self.snapshot_date.is_none() {
+            self.snapshot_date = Some(file_date(&self.dataset_path(Dataset::TitleRatings))?);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the options can be honoured and that every dataset file required by the selected tables exists.
+    /// `import` validates the options itself, this allows checking them before creating the database.
+    pub fn validate(&self) -> Result<(), ImportError> {
+        if self.fast && self.safe {
+            return Err(ImportError::Options(
+                "The fast and safe options can not be used together".to_string(),
+            ));
+        }
+
+        if self.update && self.overwrite {
+            return Err(ImportError::Options(
+                "The update and overwrite options can not be used together".to_string(),
+            ));
+        }
+
+        if self.snapshot_ratings && !self.tables.contains(&Table::TitleRating) {
+            return Err(ImportError::Options(
+                "Ratings can only be snapshot while parsing them, toggle --title_rating along with --snapshot-ratings"
+                    .to_string(),
+            ));
+        }
+
+        self.validate_table_names()?;
+        self.validate_snapshot_date()?;
+
+        let mut datasets: Vec<Dataset> = self
+            .selected_tables()
+            .iter()
+            .map(|table| table.dataset())
+            .collect();
+        if self.exclude_adult {
+            datasets.push(Dataset::TitleBasics);
+        }
+
+        let mut missing: Vec<String> = Vec::new();
+        for dataset in datasets {
+            let path = self.dataset_path(dataset);
+            if !Path::new(&path).is_file() && !missing.contains(&path) {
+                missing.push(path);
+            }
+        }
+
+        if !missing.is_empty() {
+            return Err(ImportError::io(
+                "Missing dataset files required by the selected tables",
+                io::Error::new(io::ErrorKind::NotFound, missing.join(", ")),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the snapshot date is a YYYY-MM-DD date, as it is compared with the dates stored before it.
+    pub(crate) fn validate_snapshot_date(&self) -> Result<(), ImportError> {
+        match &self.snapshot_date {
+            Some(date) if !is_date(date) => Err(ImportError::Options(format!(
+                "Invalid snapshot date {date}, it has to be a YYYY-MM-DD date"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the prefix and the renames only produce valid table names.
+    pub(crate) fn validate_table_names(&self) -> Result<(), ImportError> {
+        let names = std::iter::once(self.table_names.prefix.as_str()).chain(
+            self.table_names
+                .renames
+                .iter()
+                .map(|(_, name)| name.as_str()),
+        );
+        for (i, name) in names.enumerate() {
+            // Table names end up in the SQL statements, so only letters, digits and underscores are accepted.
+            // Only the prefix can be empty.
+            if (i > 0 && name.is_empty())
+                || name.starts_with(|c: char| c.is_ascii_digit())
+                || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                return Err(ImportError::Options(format!(
+                    "Invalid table name {name}, it has to start with a letter or an underscore and contain only letters, digits and underscores"
+                )));
+            }
+        }
+
+        // SQLite compares table names without regard to case.
+        let mut names: Vec<String> = Vec::new();
+        for name in self.table_names.dataset_tables() {
+            if names.iter().any(|other| other.eq_ignore_ascii_case(&name)) {
+                return Err(ImportError::Options(format!(
+                    "The table name {name} is given to several tables, rename one of them"
+                )));
+            }
+            names.push(name);
+        }
+
+        Ok(())
+    }
+}
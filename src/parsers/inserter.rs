@@ -0,0 +1,606 @@
+use crate::{
+    error::ImportError,
+    options::{ErrorPolicy, ImportOptions},
+    parsers::rejects::{quarantine, SkipReason, SourceLine, REJECTS_TABLE},
+    update::{delete_cascading, record_keys},
+    utils::{is_foreign_key_violation, print_line},
+};
+use sqlx::{query::Query, sqlite::SqliteArguments, Sqlite, SqliteConnection};
+use std::{collections::HashSet, fmt::Display};
+
+/// SQLite refuses statements binding more variables than this.
+const MAX_VARIABLES: usize = 32766;
+
+#[derive(PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Null,
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Integer(v) => write!(f, "{v}"),
+            Value::Real(v) => write!(f, "{v}"),
+            Value::Text(v) => write!(f, "{v}"),
+            Value::Null => write!(f, "NULL"),
+        }
+    }
+}
+
+impl From<u16> for Value {
+    fn from(v: u16) -> Self {
+        Value::Integer(v.into())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(v: u32) -> Self {
+        Value::Integer(v.into())
+    }
+}
+
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::Real(v.into())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Integer(v.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Text(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Text(v.to_string())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Value::Null, Into::into)
+    }
+}
+
+/// How rows are written when updating an existing database, the first column of every row being its key.
+enum Mode {
+    Insert,
+    /// Conflicting rows are updated in place and their keys recorded.
+    Upsert {
+        key: String,
+        /// Keys of the malformed lines since the last flush, recorded along with the keys of the rows.
+        kept: Vec<i64>,
+    },
+    /// Stored rows of a key are deleted the first time the key is seen, before the new rows are inserted.
+    Replace {
+        key: String,
+        /// Keys found in the dataset since the last flush, including those of the lines giving no row.
+        found: Vec<i64>,
+        /// Keys of the malformed lines since the last flush, whose stored rows are kept.
+        kept: Vec<i64>,
+        cleared: HashSet<i64>,
+        /// Whether the cleared and kept keys are recorded, see `track_vanished`.
+        tracked: bool,
+    },
+}
+
+/// Buffers rows of a table and inserts them with multi-row `INSERT ... VALUES (...), (...)` statements.
+///
+/// Full batches always use the same query string, so sqlx keeps it as a cached prepared statement.
+/// When a batch fails, its rows are retried one by one so the error policy only applies to the offending rows.
+pub struct BatchInserter {
+    table_name: String,
+    columns: usize,
+    batch_size: usize,
+    batch_query: String,
+    row_query: String,
+    values: Vec<Value>,
+    source: SourceLine,
+    sources: Vec<SourceLine>,
+    inserted: usize,
+    unkeyed: usize,
+    skipped: [usize; SkipReason::ALL.len()],
+    policy: ErrorPolicy,
+    rejects_table: String,
+    log: bool,
+    update: bool,
+    /// Tables whose rows are deleted along with the replaced rows referenced by them.
+    dataset_tables: Vec<String>,
+    conflict: String,
+    mode: Mode,
+}
+
+impl BatchInserter {
+    pub fn new(table_name: &str, columns: usize, options: &ImportOptions) -> Self {
+        let batch_size = options.batch_size.clamp(1, MAX_VARIABLES / columns);
+        Self {
+            table_name: table_name.to_string(),
+            columns,
+            batch_size,
+            batch_query: insert_query(table_name, columns, batch_size),
+            row_query: insert_query(table_name, columns, 1),
+            values: Vec::with_capacity(batch_size * columns),
+            source: SourceLine::default(),
+            sources: Vec::new(),
+            inserted: 0,
+            unkeyed: 0,
+            skipped: [0; SkipReason::ALL.len()],
+            policy: options.on_error,
+            rejects_table: options.table_names.internal(REJECTS_TABLE),
+            log: options.log,
+            update: options.update,
+            dataset_tables: options.table_names.dataset_tables(),
+            conflict: String::new(),
+            mode: Mode::Insert,
+        }
+    }
+
+    /// In update mode, rows conflicting on the first of the columns are updated in place, and their keys are
+    /// recorded so the rows missing from the dataset can be deleted once it is parsed.
+    pub fn upsert(mut self, columns: &[&str]) -> Self {
+        if self.update {
+            let updates: Vec<String> = columns[1..]
+                .iter()
+                .map(|c| format!("{c} = excluded.{c}"))
+                .collect();
+            self.conflict = format!(
+                " ON CONFLICT({}) DO UPDATE SET {}",
+                columns[0],
+                updates.join(", ")
+            );
+            self.batch_query = self.query(self.batch_size);
+            self.row_query = self.query(1);
+            self.mode = Mode::Upsert {
+                key: columns[0].to_string(),
+                kept: Vec::new(),
+            };
+        }
+
+        self
+    }
+
+    /// In update mode, the rows already stored for the key column, which must be the first one, are replaced
+    /// by the rows found in the dataset.
+    pub fn replace_by(mut self, key: &str) -> Self {
+        if self.update {
+            self.mode = Mode::Replace {
+                key: key.to_string(),
+                found: Vec::new(),
+                kept: Vec::new(),
+                cleared: HashSet::new(),
+                tracked: false,
+            };
+        }
+
+        self
+    }
+
+    /// In update mode, also records the keys found in the dataset, so the stored rows of the keys it has no line for
+    /// anymore can be deleted once it is parsed. Only meant for the datasets giving at least a line per key.
+    pub fn track_vanished(mut self) -> Self {
+        if let Mode::Replace { tracked, .. } = &mut self.mode {
+            *tracked = true;
+        }
+
+        self
+    }
+
+    /// Key column of the rows to delete once the dataset is parsed when their key was not recorded,
+    /// for the inserters recording their keys.
+    pub fn tracked_key(&self) -> Option<&str> {
+        match &self.mode {
+            Mode::Upsert { key, .. }
+            | Mode::Replace {
+                key, tracked: true, ..
+            } => Some(key),
+            _ => None,
+        }
+    }
+
+    /// In update mode, marks the key as found in the dataset, so the rows stored for it are deleted even when
+    /// its line gives no row anymore.
+    pub fn replace_key(&mut self, key: u32) {
+        if let Mode::Replace { found, .. } = &mut self.mode {
+            found.push(key.into());
+        }
+    }
+
+    /// In update mode, records the key of a line that could not be parsed, so the rows stored for it are kept.
+    /// Lines without a readable key are counted instead, see `unkeyed`.
+    pub fn keep_key(&mut self, key: Option<u32>) {
+        if let Mode::Upsert { kept, .. }
+        | Mode::Replace {
+            kept,
+            tracked: true,
+            ..
+        } = &mut self.mode
+        {
+            match key {
+                Some(key) => kept.push(key.into()),
+                None => self.unkeyed += 1,
+            }
+        }
+    }
+
+    /// Number of malformed lines whose key could not be read, the stored rows missing from the dataset can then
+    /// not be told apart from the rows of these lines.
+    pub fn unkeyed(&self) -> usize {
+        self.unkeyed
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// Number of rows inserted so far.
+    pub fn inserted(&self) -> usize {
+        self.inserted
+    }
+
+    /// Number of lines and rows skipped so far, indexed by `SkipReason`.
+    pub fn skipped(&self) -> [usize; SkipReason::ALL.len()] {
+        self.skipped
+    }
+
+    /// Sets the dataset line the rows pushed next are parsed from.
+    pub fn set_source(&mut self, line: &SourceLine) {
+        self.source = line.clone();
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(row.len(), self.columns);
+        self.values.extend(row);
+        if self.policy != ErrorPolicy::Skip {
+            self.sources.push(self.source.clone());
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        let found = match &self.mode {
+            Mode::Replace { found, .. } => found.len(),
+            _ => 0,
+        };
+        self.values.len() >= self.batch_size * self.columns || found >= self.batch_size
+    }
+
+    pub async fn flush(&mut self, conn: &mut SqliteConnection) -> Result<(), ImportError> {
+        if let Mode::Replace {
+            key,
+            found,
+            kept,
+            cleared,
+            tracked,
+        } = &mut self.mode
+        {
+            let keys: Vec<String> = found
+                .drain(..)
+                .chain(
+                    self.values
+                        .chunks(self.columns)
+                        .filter_map(|row| match row[0] {
+                            Value::Integer(k) => Some(k),
+                            _ => None,
+                        }),
+                )
+                .filter(|k| cleared.insert(*k))
+                .map(|k| k.to_string())
+                .collect();
+            if !keys.is_empty() {
+                let condition = format!("{key} IN ({})", keys.join(", "));
+                delete_cascading(&self.table_name, &condition, &self.dataset_tables, conn).await?;
+            }
+            if *tracked {
+                let keys: Vec<String> = keys
+                    .into_iter()
+                    .chain(kept.drain(..).map(|k| k.to_string()))
+                    .collect();
+                record_keys(&self.table_name, &keys, conn).await?;
+            }
+        }
+
+        if let Mode::Upsert { kept, .. } = &mut self.mode {
+            let keys: Vec<String> = kept.drain(..).map(|k| k.to_string()).collect();
+            record_keys(&self.table_name, &keys, conn).await?;
+        }
+
+        if self.values.is_empty() {
+            return Ok(());
+        }
+
+        let rows = self.values.len() / self.columns;
+        let partial_query;
+        let query = if rows == self.batch_size {
+            &self.batch_query
+        } else {
+            partial_query = self.query(rows);
+            &partial_query
+        };
+
+        let mut failures = Vec::new();
+        if bind_values(sqlx::query(query), &self.values)
+            .execute(&mut *conn)
+            .await
+            .is_err()
+        {
+            for (i, row) in self.values.chunks(self.columns).enumerate() {
+                if let Err(e) = bind_values(sqlx::query(&self.row_query), row)
+                    .execute(&mut *conn)
+                    .await
+                {
+                    if self.log {
+                        let row: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+                        eprintln!(
+                            "\nFailed to insert {} into {} => {e}",
+                            row.join(", "),
+                            self.table_name
+                        );
+                    }
+                    failures.push((i, e));
+                }
+            }
+        }
+
+        self.inserted += rows - failures.len();
+        for (i, e) in failures {
+            let reason = if is_foreign_key_violation(&e) {
+                SkipReason::MissingForeignKey
+            } else {
+                SkipReason::InsertFailed
+            };
+            let line = self.sources.get(i).cloned().unwrap_or_default();
+            let error = ImportError::database(
+                format!(
+                    "Unable to insert line {} of {} into {}",
+                    line.number, line.file_name, self.table_name
+                ),
+                e,
+            );
+            self.reject(reason, error, &line, conn).await?;
+        }
+
+        if let Mode::Upsert { .. } = self.mode {
+            let keys: Vec<String> = self
+                .values
+                .chunks(self.columns)
+                .map(|row| row[0].to_string())
+                .collect();
+            record_keys(&self.table_name, &keys, conn).await?;
+        }
+
+        self.values.clear();
+        self.sources.clear();
+        Ok(())
+    }
+
+    /// Applies the error policy to a line that could not be parsed or a row that could not be inserted,
+    /// failing when the import has to abort.
+    pub async fn reject(
+        &mut self,
+        reason: SkipReason,
+        error: ImportError,
+        line: &SourceLine,
+        conn: &mut SqliteConnection,
+    ) -> Result<(), ImportError> {
+        self.skipped[reason as usize] += 1;
+        match self.policy {
+            ErrorPolicy::Abort => Err(error),
+            ErrorPolicy::Skip => Ok(()),
+            ErrorPolicy::Quarantine => {
+                quarantine(
+                    &self.rejects_table,
+                    line,
+                    &self.table_name,
+                    &error.to_string(),
+                    conn,
+                )
+                .await
+            }
+        }
+    }
+
+    fn query(&self, rows: usize) -> String {
+        insert_query(&self.table_name, self.columns, rows) + &self.conflict
+    }
+
+    /// Prints how many lines and rows were skipped, per reason.
+    pub fn report_skipped(&self) {
+        for reason in SkipReason::ALL {
+            let skipped = self.skipped[reason as usize];
+            if skipped == 0 {
+                continue;
+            }
+
+            let table_name = &self.table_name;
+            match reason {
+                SkipReason::Malformed => print_line(format_args!(
+                    "-- Skipped {skipped} malformed lines of {table_name} --"
+                )),
+                SkipReason::MissingForeignKey => print_line(format_args!(
+                    "-- Skipped {skipped} rows of {table_name} with missing foreign keys --"
+                )),
+                SkipReason::InsertFailed => print_line(format_args!(
+                    "-- Skipped {skipped} rows of {table_name} failing to insert --"
+                )),
+            }
+        }
+    }
+}
+
+fn insert_query(table_name: &str, columns: usize, rows: usize) -> String {
+    let row = format!("({})", vec!["?"; columns].join(", "));
+    format!(
+        "INSERT INTO {table_name} VALUES {}",
+        vec![row.as_str(); rows].join(", ")
+    )
+}
+
+fn bind_values<'q>(
+    mut query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    values: &'q [Value],
+) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    for value in values {
+        query = match value {
+            Value::Integer(v) => query.bind(*v),
+            Value::Real(v) => query.bind(*v),
+            Value::Text(v) => query.bind(v.as_str()),
+            Value::Null => query.bind(None::<i64>),
+        };
+    }
+
+    query
+}
+
+#[cfg(test)]
+mod test {
+    use super::{insert_query, BatchInserter, Value};
+    use crate::{
+        error::{ImportError, ParseError},
+        fixtures::{count, memory_database, rows},
+        options::{ErrorPolicy, ImportOptions},
+        parsers::rejects::{self, SkipReason, SourceLine},
+    };
+    use sqlx::SqliteConnection;
+
+    #[test]
+    fn builds_multi_row_insert() {
+        assert_eq!(
+            insert_query("title_genre", 2, 3),
+            "INSERT INTO title_genre VALUES (?, ?), (?, ?), (?, ?)"
+        );
+    }
+
+    /// Database with a single title, genres referencing it.
+    async fn genre_database(options: &ImportOptions) -> SqliteConnection {
+        let mut conn = memory_database().await;
+        sqlx::raw_sql(
+            "CREATE TABLE title (id integer primary key);
+            CREATE TABLE title_genre (title_id integer not null references title(id), genre text not null);
+            INSERT INTO title VALUES (1)",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        rejects::create_table(&options.table_names, &mut conn)
+            .await
+            .unwrap();
+
+        conn
+    }
+
+    fn line(number: usize, text: &str) -> SourceLine {
+        SourceLine {
+            file_name: "title.basics.tsv".into(),
+            number,
+            text: text.into(),
+        }
+    }
+
+    /// Pushes a valid row between a row missing its title and a row missing its genre, then flushes the batch.
+    async fn insert_genres(
+        policy: ErrorPolicy,
+    ) -> (BatchInserter, SqliteConnection, Result<(), ImportError>) {
+        let options = ImportOptions::new().batch_size(3).on_error(policy);
+        let mut conn = genre_database(&options).await;
+        let mut inserter = BatchInserter::new("title_genre", 2, &options);
+        for (number, row) in [
+            (2, vec![Value::Integer(2), "Drama".into()]),
+            (3, vec![Value::Integer(1), "Short".into()]),
+            (4, vec![Value::Integer(1), Value::Null]),
+        ] {
+            inserter.set_source(&line(number, "tt0000001"));
+            inserter.push(row);
+        }
+
+        let result = inserter.flush(&mut conn).await;
+        (inserter, conn, result)
+    }
+
+    #[tokio::test]
+    async fn retries_a_failing_batch_row_by_row() {
+        let (inserter, mut conn, result) = insert_genres(ErrorPolicy::Skip).await;
+        result.unwrap();
+        assert_eq!(inserter.inserted(), 1);
+        assert_eq!(
+            rows(&mut conn, "SELECT genre FROM title_genre").await,
+            vec!["Short"]
+        );
+        assert_eq!(inserter.skipped(), [0, 1, 1]);
+        assert_eq!(count(&mut conn, "_rejects").await, 0);
+    }
+
+    #[tokio::test]
+    async fn aborts_on_the_first_failing_row() {
+        let (inserter, _, result) = insert_genres(ErrorPolicy::Abort).await;
+        let Err(ImportError::Database { message, .. }) = result else {
+            panic!("the batch did not abort");
+        };
+        assert_eq!(
+            message,
+            "Unable to insert line 2 of title.basics.tsv into title_genre"
+        );
+        assert_eq!(inserter.skipped(), [0, 1, 0]);
+    }
+
+    #[tokio::test]
+    async fn quarantines_every_failing_row() {
+        let (inserter, mut conn, result) = insert_genres(ErrorPolicy::Quarantine).await;
+        result.unwrap();
+        assert_eq!(inserter.inserted(), 1);
+        assert_eq!(
+            rows(
+                &mut conn,
+                "SELECT line_number || ' ' || table_name FROM _rejects ORDER BY line_number"
+            )
+            .await,
+            vec!["2 title_genre", "4 title_genre"]
+        );
+        let reasons = rows(
+            &mut conn,
+            "SELECT reason FROM _rejects ORDER BY line_number",
+        )
+        .await;
+        assert!(
+            reasons[0].contains("FOREIGN KEY constraint failed"),
+            "{}",
+            reasons[0]
+        );
+        assert!(
+            reasons[1].contains("NOT NULL constraint failed"),
+            "{}",
+            reasons[1]
+        );
+    }
+
+    #[tokio::test]
+    async fn quarantines_malformed_lines() {
+        let options = ImportOptions::new().on_error(ErrorPolicy::Quarantine);
+        let mut conn = genre_database(&options).await;
+        let mut inserter = BatchInserter::new("title_genre", 2, &options);
+
+        let source = line(2, "tt0000001\tshort");
+        let error = ParseError::new(3, "Missing primary_name").at("title.basics.tsv", 2);
+        inserter
+            .reject(SkipReason::Malformed, error, &source, &mut conn)
+            .await
+            .unwrap();
+        assert_eq!(inserter.skipped(), [1, 0, 0]);
+        assert_eq!(
+            rows(
+                &mut conn,
+                "SELECT file_name || ' ' || line_number || ' ' || raw_line || ' ' || reason FROM _rejects"
+            )
+            .await,
+            vec!["title.basics.tsv 2 tt0000001\tshort Failed to parse line 2 of title.basics.tsv at column 3 -> Missing primary_name"]
+        );
+    }
+}
@@ -1,4 +1,5 @@
 pub mod titles;
+pub mod title_akas;
 pub mod title_genres;
 pub mod title_ratings;
 pub mod title_directors;
@@ -9,3 +10,6 @@ pub mod title_characters;
 pub mod names;
 pub mod name_professions;
 pub mod name_titles;
+pub mod inserter;
+pub mod pipeline;
+pub mod rejects;
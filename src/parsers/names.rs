@@ -1,116 +1,62 @@
-use std::{
-    fs::File,
-    io::{BufRead, BufReader, Seek},
+use crate::{
+    error::ImportError,
+    options::ImportOptions,
+    parsers::inserter::{BatchInserter, Value},
+    records::NameBasics,
+    utils::{drop_table, execute_sql},
 };
-
-use crate::{config::Args, utils::percentage_printer};
-use sqlx::{Connection, SqliteConnection};
-
-pub struct Name {
-    pub id: u32,
-    pub name: String,
-    pub birth_date: Option<u16>,
-    pub death_date: Option<u16>,
-}
-
-impl Name {
-    fn from(line: String) -> Result<Self, String> {
-        let values: Vec<&str> = line.split('\t').collect();
-        let id: u32 = values
-            .first()
-            .and_then(|s| s.get(2..))
-            .and_then(|s| s.parse().ok())
-            .ok_or(format!("Failed to parse id from {line}"))?;
-
-        let name = values
-            .get(1)
-            .map(|s| s.to_string())
-            .ok_or(format!("Failed to parse name from {line}"))?;
-
-        let birth_date = values.get(2).and_then(|v| v.parse::<u16>().ok());
-        let death_date = values.get(3).and_then(|v| v.parse::<u16>().ok());
-
-        Ok(Self {
-            id,
-            name,
-            birth_date,
-            death_date,
-        })
+use sqlx::SqliteConnection;
+
+impl NameBasics {
+    /// Values of the row in the order of `COLUMNS`, followed by the nconst when it is kept.
+    pub(crate) fn to_row(&self, imdb_ids: bool) -> Vec<Value> {
+        let mut row = vec![
+            self.id.into(),
+            self.name.as_str().into(),
+            self.birth_date.into(),
+            self.death_date.into(),
+        ];
+        if imdb_ids {
+            row.push(self.nconst.as_str().into());
+        }
+
+        row
     }
 }
 
-pub async fn parse_names(
-    file_name: &str,
-    table_name: &str,
-    conn: &mut SqliteConnection,
-    args: &Args,
-) -> Result<(), String> {
-    create_table(table_name, conn, args.overwrite).await?;
-
-    let file =
-        File::open(file_name).map_err(|e| format!("Unable to read from {file_name} -> {e}"))?;
-    let mut reader = BufReader::new(file);
-    let count = (&mut reader).lines().skip(1).count();
-    println!("-- Inserting {count} entries into {table_name} --");
-    reader
-        .rewind()
-        .map_err(|e| format!("Failed to read file {file_name} after counting => {e}"))?;
-
-    let mut tx = conn
-        .begin()
-        .await
-        .map_err(|e| format!("Failed to start transaction => {e}"))?;
-
-    let query = format!("INSERT INTO {table_name} VALUES($1, $2, $3, $4)");
-    for (i, name) in reader
-        .lines()
-        .skip(1)
-        .map(|l| l.map_err(|e| format!("Unable to read line -> {e}")))
-        .map(|l| l.and_then(Name::from))
-        .enumerate()
-    {
-        let name = name?;
-        let _ = sqlx::query(&query)
-            .bind(name.id)
-            .bind(&name.name)
-            .bind(name.birth_date)
-            .bind(name.death_date)
-            .execute(&mut *tx)
-            .await
-            .map_err(|e| {
-                if args.log {
-                    eprintln!(
-                        "\nFailed to insert {}, {}, {:?}, {:?} into {table_name} => {e}",
-                        name.id, name.name, name.birth_date, name.death_date
-                    );
-                }
-            });
+pub const COLUMNS: [&str; 4] = ["id", "name", "birth_year", "death_year"];
 
-        percentage_printer(i, count);
+pub fn inserters(table_name: &str, options: &ImportOptions) -> Vec<BatchInserter> {
+    let mut columns = COLUMNS.to_vec();
+    if options.imdb_ids {
+        columns.push("nconst");
     }
-    println!();
 
-    tx.commit()
-        .await
-        .map_err(|e| format!("Failed to commit transactions => {e}"))?;
+    vec![BatchInserter::new(table_name, columns.len(), options).upsert(&columns)]
+}
 
-    Ok(())
+pub fn push_rows(name: &NameBasics, inserters: &mut [BatchInserter], imdb_ids: bool) {
+    inserters[0].push(name.to_row(imdb_ids));
 }
 
-async fn create_table(
+pub async fn create_table(
     table_name: &str,
     conn: &mut SqliteConnection,
     overwrite: bool,
-) -> Result<(), String> {
+    imdb_ids: bool,
+) -> Result<(), ImportError> {
     if overwrite {
-        let _ = sqlx::raw_sql(format!("DROP TABLE {table_name}").as_str())
-            .execute(&mut *conn)
-            .await;
+        drop_table(table_name, conn).await;
     }
 
-    sqlx::raw_sql(format!("CREATE TABLE IF NOT EXISTS {table_name} (id integer primary key, name text not null, birth_year integer, death_year integer)").as_str())
-        .execute(conn)
-        .await.map_err(|e| format!("Unable to create {table_name} table -> {e}"))?;
+    let nconst_column = if imdb_ids {
+        ", nconst text not null unique"
+    } else {
+        ""
+    };
+
+    execute_sql(format!("CREATE TABLE IF NOT EXISTS {table_name} (id integer primary key, name text not null, birth_year integer, death_year integer{nconst_column})").as_str(), conn)
+        .await.map_err(|e| ImportError::database(format!("Unable to create {table_name} table"), e))?;
 
     Ok(())
 }
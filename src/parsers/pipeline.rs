@@ -0,0 +1,461 @@
+use crate::{
+    dataset::{Dataset, Table},
+    error::{ImportError, ParseError},
+    options::{ErrorPolicy, ImportOptions},
+    parsers::{
+        inserter::BatchInserter,
+        rejects::{SkipReason, SourceLine},
+        *,
+    },
+    records::{
+        line_id, NameBasics, Record, TitleAka, TitleBasics, TitleCrew, TitleEpisode,
+        TitlePrincipal, TitleRating,
+    },
+    report::TableReport,
+    tsv::{read_header, DatasetHeader},
+    update,
+    utils::{count_lines, has_column, open_dataset, percentage_printer, print_line, table_exists},
+};
+use sqlx::{Connection, SqliteConnection};
+use std::{collections::HashSet, io::BufRead, sync::Arc, time::Instant};
+
+struct TableLoad {
+    table: Table,
+    inserters: Vec<BatchInserter>,
+}
+
+/// A line parsed once, then handed to every table derived from its dataset.
+enum DatasetRecord {
+    TitleBasics(TitleBasics),
+    NameBasics(NameBasics),
+    TitleAka(TitleAka),
+    TitleRating(TitleRating),
+    TitleCrew(TitleCrew),
+    TitleEpisode(TitleEpisode),
+    TitlePrincipal(TitlePrincipal),
+}
+
+impl DatasetRecord {
+    fn parse(header: &DatasetHeader, line: &str) -> Result<Self, ParseError> {
+        Ok(match header.dataset() {
+            Dataset::TitleBasics => Self::TitleBasics(TitleBasics::parse_line(header, line)?),
+            Dataset::NameBasics => Self::NameBasics(NameBasics::parse_line(header, line)?),
+            Dataset::TitleAkas => Self::TitleAka(TitleAka::parse_line(header, line)?),
+            Dataset::TitleRatings => Self::TitleRating(TitleRating::parse_line(header, line)?),
+            Dataset::TitleCrew => Self::TitleCrew(TitleCrew::parse_line(header, line)?),
+            Dataset::TitleEpisode => Self::TitleEpisode(TitleEpisode::parse_line(header, line)?),
+            Dataset::TitlePrincipals => {
+                Self::TitlePrincipal(TitlePrincipal::parse_line(header, line)?)
+            }
+        })
+    }
+}
+
+/// Reads the dataset file once, fanning each line out to every selected table derived from it.
+/// The tables must be given in parsing order, so parent rows are always inserted before the rows referencing them.
+/// Returns the report of every table inserted into, including the ones derived from a selected table.
+pub async fn parse_dataset(
+    file_name: &str,
+    tables: &[Table],
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+    excluded_titles: &HashSet<u32>,
+) -> Vec<TableReport> {
+    let start = Instant::now();
+    let mut reports = Vec::new();
+    let mut loads = Vec::new();
+    for &table in tables {
+        let created = match check_references(table, conn, options).await {
+            Ok(()) => match check_imdb_ids(table, conn, options).await {
+                Ok(()) => create_table(table, conn, options).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        match created {
+            Ok(()) => loads.push(TableLoad {
+                table,
+                inserters: inserters(table, options),
+            }),
+            Err(str) => reports.push(TableReport::failed(
+                table,
+                options.table_names.get(table),
+                start.elapsed(),
+                str,
+            )),
+        }
+    }
+
+    if loads.is_empty() {
+        return reports;
+    }
+
+    let mut lines_read = 0;
+    let result = load_rows(
+        file_name,
+        &mut loads,
+        conn,
+        options,
+        excluded_titles,
+        &mut lines_read,
+    )
+    .await;
+    let duration = start.elapsed();
+    for load in &loads {
+        for inserter in &load.inserters {
+            reports.push(TableReport {
+                table: load.table,
+                table_name: inserter.table_name().to_string(),
+                lines_read,
+                // A failed dataset is rolled back as a whole.
+                rows_inserted: if result.is_ok() {
+                    inserter.inserted()
+                } else {
+                    0
+                },
+                skipped: inserter.skipped(),
+                duration,
+                error: result.clone().err(),
+            });
+        }
+    }
+
+    reports
+}
+
+async fn load_rows(
+    file_name: &str,
+    loads: &mut [TableLoad],
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+    excluded_titles: &HashSet<u32>,
+    lines_read: &mut usize,
+) -> Result<(), ImportError> {
+    let table_names: Vec<&str> = loads
+        .iter()
+        .flat_map(|load| load.inserters.iter())
+        .map(|inserter| inserter.table_name())
+        .collect();
+    // Counting the lines reads the whole dataset, so it is only done to show the progress.
+    let count = if options.progress {
+        count_lines(file_name)?
+    } else {
+        0
+    };
+    options.print_step(format_args!(
+        "-- Inserting {count} entries into {} --",
+        table_names.join(", ")
+    ));
+    let dataset = loads[0].table.dataset();
+
+    for inserter in loads
+        .iter()
+        .flat_map(|load| load.inserters.iter())
+        .filter(|inserter| inserter.tracked_key().is_some())
+    {
+        update::track_keys(inserter.table_name(), conn).await?;
+    }
+
+    let mut tx = conn
+        .begin()
+        .await
+        .map_err(|e| ImportError::database("Failed to start transaction", e))?;
+
+    // Keeping the source of every row is only needed to report or quarantine the failing ones.
+    let track_sources = options.on_error != ErrorPolicy::Skip;
+    let file_name: Arc<str> = Arc::from(file_name);
+    let mut lines = open_dataset(&file_name)?.lines();
+    let header = read_header(&mut lines, dataset, &file_name)?;
+    for (i, line) in lines.enumerate() {
+        let line = line.map_err(|e| {
+            ImportError::io(format!("Unable to read line {} of {file_name}", i + 2), e)
+        })?;
+        *lines_read += 1;
+        let source = if track_sources {
+            SourceLine {
+                file_name: file_name.clone(),
+                // Line numbers start at 1 and the header line is skipped.
+                number: i + 2,
+                text: Arc::from(line.as_str()),
+            }
+        } else {
+            SourceLine::default()
+        };
+
+        let record = DatasetRecord::parse(&header, &line);
+        let malformed_id = match &record {
+            Ok(_) => None,
+            Err(_) => line_id(&header, &line),
+        };
+        for load in loads.iter_mut() {
+            if track_sources {
+                for inserter in load.inserters.iter_mut() {
+                    inserter.set_source(&source);
+                }
+            }
+
+            match &record {
+                Ok(record) => push_rows(
+                    load.table,
+                    record,
+                    &mut load.inserters,
+                    options,
+                    excluded_titles,
+                ),
+                Err(e) => {
+                    load.inserters[0].keep_key(malformed_id);
+                    let error = e.clone().at(&file_name, i + 2);
+                    load.inserters[0]
+                        .reject(SkipReason::Malformed, error, &source, &mut tx)
+                        .await?
+                }
+            }
+        }
+
+        // Every batch is flushed together, in table order, so no row is inserted before its parent.
+        if loads
+            .iter()
+            .flat_map(|load| load.inserters.iter())
+            .any(|inserter| inserter.is_full())
+        {
+            for inserter in loads.iter_mut().flat_map(|load| load.inserters.iter_mut()) {
+                inserter.flush(&mut tx).await?;
+            }
+        }
+
+        if options.progress {
+            percentage_printer(i, count);
+        }
+    }
+
+    for inserter in loads.iter_mut().flat_map(|load| load.inserters.iter_mut()) {
+        inserter.flush(&mut tx).await?;
+    }
+    if options.progress {
+        print_line("");
+        for inserter in loads.iter().flat_map(|load| load.inserters.iter()) {
+            inserter.report_skipped();
+        }
+    }
+
+    let dataset_tables = options.table_names.dataset_tables();
+    for inserter in loads.iter().flat_map(|load| load.inserters.iter()) {
+        let Some(key) = inserter.tracked_key() else {
+            continue;
+        };
+        let table_name = inserter.table_name();
+        let unkeyed = inserter.unkeyed();
+        if unkeyed > 0 {
+            options.print_step(format_args!(
+                "-- Kept the rows of {table_name} missing from the dataset, as {unkeyed} malformed lines have no readable id --"
+            ));
+            continue;
+        }
+
+        let deleted = update::delete_vanished(table_name, key, &dataset_tables, &mut tx).await?;
+        options.print_step(format_args!(
+            "-- Deleted {deleted} rows of {table_name} missing from the dataset --"
+        ));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| ImportError::database("Failed to commit transactions", e))?;
+
+    Ok(())
+}
+
+/// Joining tables can only be inserted into when the core tables they reference exist.
+async fn check_references(
+    table: Table,
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+) -> Result<(), ImportError> {
+    for &parent in table.references() {
+        let parent_name = options.table_names.get(parent);
+        if !table_exists(&parent_name, conn).await? {
+            return Err(ImportError::Schema(format!(
+                "Skipping {} as it references the {parent_name} table which does not exist, toggle --{} to parse it first",
+                options.table_names.get(table),
+                parent.name()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// The title and name tables only have the tconst and nconst columns when created with --imdb-ids, so an existing
+/// table can only be imported into with the option it was created with.
+async fn check_imdb_ids(
+    table: Table,
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+) -> Result<(), ImportError> {
+    let column = match table {
+        Table::Title => "tconst",
+        Table::Name => "nconst",
+        _ => return Ok(()),
+    };
+
+    let table_name = options.table_names.get(table);
+    if options.overwrite || !table_exists(&table_name, conn).await? {
+        return Ok(());
+    }
+
+    let stored = has_column(&table_name, column, conn).await?;
+    if stored != options.imdb_ids {
+        let (created, hint) = if stored {
+            ("with", "toggle --imdb-ids")
+        } else {
+            ("without", "leave out --imdb-ids")
+        };
+        return Err(ImportError::Schema(format!(
+            "The {table_name} table was created {created} the {column} column, {hint} to import into it or overwrite it"
+        )));
+    }
+
+    Ok(())
+}
+
+async fn create_table(
+    table: Table,
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+) -> Result<(), ImportError> {
+    let table_name = &options.table_names.get(table);
+    match table {
+        Table::Name => {
+            names::create_table(table_name, conn, options.overwrite, options.imdb_ids).await
+        }
+        Table::Title => {
+            titles::create_table(table_name, conn, options.overwrite, options.imdb_ids).await
+        }
+        Table::NameProfession => {
+            name_professions::create_table(
+                table_name,
+                conn,
+                options.overwrite,
+                &options.table_names,
+            )
+            .await
+        }
+        Table::NameTitle => {
+            name_titles::create_table(table_name, conn, options.overwrite, &options.table_names)
+                .await
+        }
+        Table::TitleGenre => {
+            title_genres::create_table(table_name, conn, options.overwrite, &options.table_names)
+                .await
+        }
+        Table::TitleAka => {
+            title_akas::create_table(table_name, conn, options.overwrite, &options.table_names)
+                .await
+        }
+        Table::TitleRating => {
+            title_ratings::create_table(
+                table_name,
+                conn,
+                options.overwrite,
+                &options.table_names,
+                options.ratings_snapshot_date(),
+            )
+            .await
+        }
+        Table::TitleDirector => {
+            title_directors::create_table(table_name, conn, options.overwrite, &options.table_names)
+                .await
+        }
+        Table::TitleWriter => {
+            title_writers::create_table(table_name, conn, options.overwrite, &options.table_names)
+                .await
+        }
+        Table::TitleEpisode => {
+            title_episodes::create_table(table_name, conn, options.overwrite, &options.table_names)
+                .await
+        }
+        Table::TitleJob => {
+            title_jobs::create_table(table_name, conn, options.overwrite, &options.table_names)
+                .await
+        }
+        Table::TitleCharacter => {
+            title_characters::create_table(
+                table_name,
+                conn,
+                options.overwrite,
+                &options.table_names,
+            )
+            .await
+        }
+    }
+}
+
+fn inserters(table: Table, options: &ImportOptions) -> Vec<BatchInserter> {
+    let table_name = &options.table_names.get(table);
+    match table {
+        Table::Name => names::inserters(table_name, options),
+        Table::Title => titles::inserters(table_name, options),
+        Table::NameProfession => name_professions::inserters(table_name, options),
+        Table::NameTitle => name_titles::inserters(table_name, options),
+        Table::TitleGenre => title_genres::inserters(table_name, options),
+        Table::TitleAka => title_akas::inserters(table_name, options),
+        Table::TitleRating => title_ratings::inserters(table_name, options),
+        Table::TitleDirector => title_directors::inserters(table_name, options),
+        Table::TitleWriter => title_writers::inserters(table_name, options),
+        Table::TitleEpisode => title_episodes::inserters(table_name, options),
+        Table::TitleJob => title_jobs::inserters(table_name, options),
+        Table::TitleCharacter => title_characters::inserters(table_name, options),
+    }
+}
+
+fn push_rows(
+    table: Table,
+    record: &DatasetRecord,
+    inserters: &mut [BatchInserter],
+    options: &ImportOptions,
+    excluded_titles: &HashSet<u32>,
+) {
+    match (table, record) {
+        (Table::Name, DatasetRecord::NameBasics(name)) => {
+            names::push_rows(name, inserters, options.imdb_ids)
+        }
+        (Table::Title, DatasetRecord::TitleBasics(title)) => {
+            titles::push_rows(title, inserters, excluded_titles, options.imdb_ids)
+        }
+        (Table::NameProfession, DatasetRecord::NameBasics(name)) => {
+            name_professions::push_rows(name, inserters)
+        }
+        (Table::NameTitle, DatasetRecord::NameBasics(name)) => {
+            name_titles::push_rows(name, inserters, excluded_titles)
+        }
+        (Table::TitleGenre, DatasetRecord::TitleBasics(title)) => {
+            title_genres::push_rows(title, inserters, excluded_titles)
+        }
+        (Table::TitleAka, DatasetRecord::TitleAka(title_aka)) => {
+            title_akas::push_rows(title_aka, inserters, excluded_titles)
+        }
+        (Table::TitleRating, DatasetRecord::TitleRating(title_rating)) => title_ratings::push_rows(
+            title_rating,
+            inserters,
+            excluded_titles,
+            options.ratings_snapshot_date(),
+        ),
+        (Table::TitleDirector, DatasetRecord::TitleCrew(title_crew)) => {
+            title_directors::push_rows(title_crew, inserters, excluded_titles)
+        }
+        (Table::TitleWriter, DatasetRecord::TitleCrew(title_crew)) => {
+            title_writers::push_rows(title_crew, inserters, excluded_titles)
+        }
+        (Table::TitleEpisode, DatasetRecord::TitleEpisode(title_episode)) => {
+            title_episodes::push_rows(title_episode, inserters, excluded_titles)
+        }
+        (Table::TitleJob, DatasetRecord::TitlePrincipal(title_principal)) => {
+            title_jobs::push_rows(title_principal, inserters, excluded_titles)
+        }
+        (Table::TitleCharacter, DatasetRecord::TitlePrincipal(title_principal)) => {
+            title_characters::push_rows(title_principal, inserters, excluded_titles)
+        }
+        _ => unreachable!("{} is not parsed from this dataset", table.name()),
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{error::ImportError, options::TableNames, utils::execute_sql};
+use sqlx::SqliteConnection;
+use std::sync::Arc;
+
+/// Table the quarantined lines are written to.
+pub const REJECTS_TABLE: &str = "_rejects";
+
+/// Dataset line the rows being inserted were parsed from.
+#[derive(Clone, Default)]
+pub struct SourceLine {
+    pub file_name: Arc<str>,
+    pub number: usize,
+    pub text: Arc<str>,
+}
+
+/// Why a line or a row was skipped, the skipped counts of `TableReport` being indexed by it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The line could not be parsed.
+    Malformed,
+    /// The row references a title or a name that is not in the database.
+    MissingForeignKey,
+    /// The row was refused by the database for another reason.
+    InsertFailed,
+}
+
+impl SkipReason {
+    /// Every reason, in the order of the skipped counts.
+    pub const ALL: [SkipReason; 3] = [
+        SkipReason::Malformed,
+        SkipReason::MissingForeignKey,
+        SkipReason::InsertFailed,
+    ];
+
+    /// Name of the reason in the report and the _rejects table.
+    pub fn name(self) -> &'static str {
+        match self {
+            SkipReason::Malformed => "malformed",
+            SkipReason::MissingForeignKey => "missing_foreign_key",
+            SkipReason::InsertFailed => "insert_failed",
+        }
+    }
+}
+
+pub async fn create_table(
+    names: &TableNames,
+    conn: &mut SqliteConnection,
+) -> Result<(), ImportError> {
+    let rejects_table = names.internal(REJECTS_TABLE);
+    execute_sql(format!("CREATE TABLE IF NOT EXISTS {rejects_table} (file_name text not null, line_number integer not null, table_name text not null, raw_line text not null, reason text not null)").as_str(), conn)
+        .await
+        .map_err(|e| ImportError::database(format!("Unable to create {rejects_table} table"), e))?;
+
+    Ok(())
+}
+
+/// Writes the line that could not be parsed or inserted into the rejects table.
+pub async fn quarantine(
+    rejects_table: &str,
+    line: &SourceLine,
+    table_name: &str,
+    reason: &str,
+    conn: &mut SqliteConnection,
+) -> Result<(), ImportError> {
+    sqlx::query(&format!(
+        "INSERT INTO {rejects_table} VALUES ($1, $2, $3, $4, $5)"
+    ))
+    .bind(line.file_name.as_ref())
+    .bind(line.number as i64)
+    .bind(table_name)
+    .bind(line.text.as_ref())
+    .bind(reason)
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        ImportError::database(
+            format!(
+                "Unable to quarantine line {} of {}",
+                line.number, line.file_name
+            ),
+            e,
+        )
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use crate::{
+    dataset::Table,
+    error::ImportError,
+    options::{ImportOptions, TableNames},
+    parsers::inserter::BatchInserter,
+    records::TitleAka,
+    utils::{drop_table, execute_sql},
+};
+use sqlx::SqliteConnection;
+use std::collections::HashSet;
+
+/// Inserters of title_aka followed by its type and attribute child tables.
+pub fn inserters(table_name: &str, options: &ImportOptions) -> Vec<BatchInserter> {
+    vec![
+        BatchInserter::new(table_name, 6, options)
+            .replace_by("title_id")
+            .track_vanished(),
+        BatchInserter::new(&format!("{table_name}_type"), 3, options),
+        BatchInserter::new(&format!("{table_name}_attribute"), 3, options),
+    ]
+}
+
+pub fn push_rows(
+    title_aka: &TitleAka,
+    inserters: &mut [BatchInserter],
+    excluded_titles: &HashSet<u32>,
+) {
+    if excluded_titles.contains(&title_aka.title_id) {
+        return;
+    }
+
+    inserters[0].push(vec![
+        title_aka.title_id.into(),
+        title_aka.ordering.into(),
+        title_aka.title.as_str().into(),
+        title_aka.region.as_deref().into(),
+        title_aka.language.as_deref().into(),
+        title_aka.is_original_title.into(),
+    ]);
+
+    for value in &title_aka.types {
+        inserters[1].push(vec![
+            title_aka.title_id.into(),
+            title_aka.ordering.into(),
+            value.as_str().into(),
+        ]);
+    }
+
+    for value in &title_aka.attributes {
+        inserters[2].push(vec![
+            title_aka.title_id.into(),
+            title_aka.ordering.into(),
+            value.as_str().into(),
+        ]);
+    }
+}
+
+pub async fn create_table(
+    table_name: &str,
+    conn: &mut SqliteConnection,
+    overwrite: bool,
+    names: &TableNames,
+) -> Result<(), ImportError> {
+    let title_table = names.get(Table::Title);
+    let type_table = format!("{table_name}_type");
+    let attribute_table = format!("{table_name}_attribute");
+    if overwrite {
+        for table in [type_table.as_str(), attribute_table.as_str(), table_name] {
+            drop_table(table, conn).await;
+        }
+    }
+
+    execute_sql(format!("CREATE TABLE IF NOT EXISTS {table_name} (title_id integer not null, ordering integer not null, title text not null, region text, language text, is_original_title boolean not null, primary key(title_id, ordering), foreign key(title_id) references {title_table}(id))").as_str(), &mut *conn)
+        .await.map_err(|e| ImportError::database(format!("Unable to create {table_name} table"), e))?;
+
+    execute_sql(format!("CREATE TABLE IF NOT EXISTS {type_table} (title_id integer not null, ordering integer not null, type text not null, foreign key(title_id, ordering) references {table_name}(title_id, ordering))").as_str(), &mut *conn)
+        .await.map_err(|e| ImportError::database(format!("Unable to create {type_table} table"), e))?;
+
+    execute_sql(format!("CREATE TABLE IF NOT EXISTS {attribute_table} (title_id integer not null, ordering integer not null, attribute text not null, foreign key(title_id, ordering) references {table_name}(title_id, ordering))").as_str(), conn)
+        .await.map_err(|e| ImportError::database(format!("Unable to create {attribute_table} table"), e))?;
+
+    Ok(())
+}
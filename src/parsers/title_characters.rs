@@ -1,114 +1,55 @@
-use crate::{config::Args, utils::*};
-use sqlx::{Connection, SqliteConnection};
-use std::{
-    fs::File,
-    io::{BufRead, BufReader, Seek},
+use crate::{
+    dataset::Table,
+    error::ImportError,
+    options::{ImportOptions, TableNames},
+    parsers::inserter::BatchInserter,
+    records::TitlePrincipal,
+    utils::{drop_table, execute_sql},
 };
+use sqlx::SqliteConnection;
+use std::collections::HashSet;
 
-pub struct TitleCharacters {
-    title_id: u32,
-    name_id: u32,
-    characters: Vec<String>,
+pub fn inserters(table_name: &str, options: &ImportOptions) -> Vec<BatchInserter> {
+    vec![BatchInserter::new(table_name, 4, options)
+        .replace_by("title_id")
+        .track_vanished()]
 }
 
-impl TitleCharacters {
-    fn from(line: String) -> Result<Self, String> {
-        let values: Vec<&str> = line.split('\t').collect();
-        let title_id: u32 = values
-            .first()
-            .and_then(|s| s.get(2..))
-            .and_then(|s| s.parse().ok())
-            .ok_or(format!("Failed to parse title_id from {line}"))?;
-
-        let name_id = values
-            .get(2)
-            .and_then(|s| s.get(2..))
-            .and_then(|s| s.parse().ok())
-            .ok_or(format!("Failed to parse name_id from {line}"))?;
-
-        let characters = values.get(5).map(|&s| find_strings(s)).unwrap_or_default();
-
-        Ok(Self {
-            title_id,
-            name_id,
-            characters,
-        })
+pub fn push_rows(
+    title_principal: &TitlePrincipal,
+    inserters: &mut [BatchInserter],
+    excluded_titles: &HashSet<u32>,
+) {
+    if excluded_titles.contains(&title_principal.title_id) {
+        return;
     }
-}
-
-pub async fn parse_title_characters(
-    file_name: &str,
-    table_name: &str,
-    conn: &mut SqliteConnection,
-    args: &Args,
-) -> Result<(), String> {
-    create_table(table_name, conn, args.overwrite).await?;
-    let file =
-        File::open(file_name).map_err(|e| format!("Unable to read from {file_name} -> {e}"))?;
-    let mut reader = BufReader::new(file);
-    let count = (&mut reader).lines().skip(1).count();
-    println!("-- Inserting {count} entries into {table_name} --");
 
-    reader
-        .rewind()
-        .map_err(|e| format!("Failed to read file {file_name} after counting => {e}"))?;
-
-    let mut tx = conn
-        .begin()
-        .await
-        .map_err(|e| format!("Failed to start transaction => {e}"))?;
-
-    for (i, title_characters) in reader
-        .lines()
-        .skip(1)
-        .map(|l| l.map_err(|e| format!("Unable to read line -> {e}")))
-        .map(|l| l.and_then(TitleCharacters::from))
-        .enumerate()
-    {
-        let title_characters = title_characters?;
-        let query = format!("INSERT INTO {table_name} VALUES($1, $2, $3)");
-        for character in title_characters.characters {
-            let _ = sqlx::query(&query)
-                .bind(title_characters.title_id)
-                .bind(title_characters.name_id)
-                .bind(&character)
-                .execute(&mut *tx)
-                .await
-                .inspect_err(|e| {
-                    if args.log {
-                        eprintln!(
-                            "\nFailed to insert {}, {}, {} into {table_name} => {e}",
-                            title_characters.title_id, title_characters.name_id, character,
-                        );
-                    }
-                });
-        }
-
-        percentage_printer(i, count);
+    inserters[0].replace_key(title_principal.title_id);
+    for character in &title_principal.characters {
+        inserters[0].push(vec![
+            title_principal.title_id.into(),
+            title_principal.ordering.into(),
+            title_principal.name_id.into(),
+            character.as_str().into(),
+        ]);
     }
-    println!();
-
-    tx.commit()
-        .await
-        .map_err(|e| format!("Failed to commit transactions => {e}"))?;
-
-    Ok(())
 }
 
-async fn create_table(
+pub async fn create_table(
     table_name: &str,
     conn: &mut SqliteConnection,
     overwrite: bool,
-) -> Result<(), String> {
+    names: &TableNames,
+) -> Result<(), ImportError> {
+    let title_table = names.get(Table::Title);
+    let name_table = names.get(Table::Name);
+
     if overwrite {
-        let _ = sqlx::raw_sql(format!("DROP TABLE {table_name}").as_str())
-            .execute(&mut *conn)
-            .await;
+        drop_table(table_name, conn).await;
     }
 
-    sqlx::raw_sql(format!("CREATE TABLE IF NOT EXISTS {table_name} (title_id integer not null, name_id integer not null, character text not null, foreign key(title_id) references title(id), foreign key(name_id) references name(id))").as_str())
-        .execute(conn)
-        .await.map_err(|e| format!("Unable to create {table_name} table -> {e}"))?;
+    execute_sql(format!("CREATE TABLE IF NOT EXISTS {table_name} (title_id integer not null, ordering integer not null, name_id integer not null, character text not null, primary key(title_id, ordering, character), foreign key(title_id) references {title_table}(id), foreign key(name_id) references {name_table}(id))").as_str(), conn)
+        .await.map_err(|e| ImportError::database(format!("Unable to create {table_name} table"), e))?;
 
     Ok(())
 }
@@ -1,119 +1,66 @@
-use crate::{config::Args, utils::percentage_printer};
-use sqlx::{Connection, SqliteConnection};
-use std::{
-    fs::File,
-    io::{BufRead, BufReader, Seek},
+use crate::{
+    dataset::Table,
+    error::ImportError,
+    options::{ImportOptions, TableNames},
+    parsers::inserter::{BatchInserter, Value},
+    records::TitleEpisode,
+    utils::{drop_table, execute_sql},
 };
-
-struct TitleEpisode {
-    title_episode_id: u32,
-    title_series_id: u32,
-    season_number: Option<u32>,
-    episode_number: Option<u32>,
-}
+use sqlx::SqliteConnection;
+use std::collections::HashSet;
 
 impl TitleEpisode {
-    fn from(line: String) -> Result<Self, String> {
-        let values: Vec<&str> = line.split('\t').collect();
-        let title_episode_id: u32 = values
-            .first()
-            .and_then(|s| s.get(2..))
-            .and_then(|s| s.parse().ok())
-            .ok_or(format!("Failed to parse title_episode_id from {line}"))?;
-
-        let title_series_id: u32 = values
-            .get(1)
-            .and_then(|s| s.get(2..))
-            .and_then(|s| s.parse().ok())
-            .ok_or(format!("Failed to parse title_series_id from {line}"))?;
-
-        let season_number = values.get(2).and_then(|s| s.parse().ok());
-        let episode_number = values.get(3).and_then(|s| s.parse().ok());
-
-        Ok(Self {
-            title_episode_id,
-            title_series_id,
-            season_number,
-            episode_number,
-        })
+    pub(crate) fn to_row(&self) -> Vec<Value> {
+        vec![
+            self.title_episode_id.into(),
+            self.title_series_id.into(),
+            self.episode_number.into(),
+            self.season_number.into(),
+        ]
     }
 }
 
-pub async fn parse_title_episodes(
-    file_name: &str,
-    table_name: &str,
-    conn: &mut SqliteConnection,
-    args: &Args,
-) -> Result<(), String> {
-    create_table(table_name, conn, args.overwrite).await?;
-    let file =
-        File::open(file_name).map_err(|e| format!("Unable to read from {file_name} -> {e}"))?;
-    let mut reader = BufReader::new(file);
-    let count = (&mut reader).lines().skip(1).count();
-    println!("-- Inserting {count} entries into {table_name} --");
+pub const COLUMNS: [&str; 4] = [
+    "title_episode_id",
+    "title_series_id",
+    "episode_number",
+    "season_number",
+];
 
-    reader
-        .rewind()
-        .map_err(|e| format!("Failed to read file {file_name} after counting => {e}"))?;
-
-    let mut tx = conn
-        .begin()
-        .await
-        .map_err(|e| format!("Failed to start transaction => {e}"))?;
+pub fn inserters(table_name: &str, options: &ImportOptions) -> Vec<BatchInserter> {
+    vec![BatchInserter::new(table_name, 4, options)
+        .replace_by("title_episode_id")
+        .track_vanished()]
+}
 
-    for (i, title_episode) in reader
-        .lines()
-        .skip(1)
-        .map(|l| l.map_err(|e| format!("Unable to read line -> {e}")))
-        .map(|l| l.and_then(TitleEpisode::from))
-        .enumerate()
+pub fn push_rows(
+    title_episode: &TitleEpisode,
+    inserters: &mut [BatchInserter],
+    excluded_titles: &HashSet<u32>,
+) {
+    if excluded_titles.contains(&title_episode.title_episode_id)
+        || excluded_titles.contains(&title_episode.title_series_id)
     {
-        let title_episode = title_episode?;
-        let query = format!("INSERT INTO {table_name} VALUES($1, $2, $3, $4)");
-        let _ = sqlx::query(&query)
-            .bind(title_episode.title_episode_id)
-            .bind(title_episode.title_series_id)
-            .bind(title_episode.season_number)
-            .bind(title_episode.episode_number)
-            .execute(&mut *tx)
-            .await
-            .inspect_err(|e| {
-                if args.log {
-                    eprintln!(
-                        "\nFailed to insert {}, {}, {:?}, {:?}, into {table_name} => {e}",
-                        title_episode.title_episode_id,
-                        title_episode.title_series_id,
-                        title_episode.episode_number,
-                        title_episode.season_number
-                    );
-                }
-            });
-
-        percentage_printer(i, count);
+        return;
     }
-    println!();
 
-    tx.commit()
-        .await
-        .map_err(|e| format!("Failed to commit transactions => {e}"))?;
-
-    Ok(())
+    inserters[0].push(title_episode.to_row());
 }
 
-async fn create_table(
+pub async fn create_table(
     table_name: &str,
     conn: &mut SqliteConnection,
     overwrite: bool,
-) -> Result<(), String> {
+    names: &TableNames,
+) -> Result<(), ImportError> {
+    let title_table = names.get(Table::Title);
+
     if overwrite {
-        let _ = sqlx::raw_sql(format!("DROP TABLE {table_name}").as_str())
-            .execute(&mut *conn)
-            .await;
+        drop_table(table_name, conn).await;
     }
 
-    sqlx::raw_sql(format!("CREATE TABLE IF NOT EXISTS {table_name} (title_episode_id integer not null, title_series_id integer not null, episode_number integer, season_number integer, foreign key(title_episode_id) references title(id), foreign key(title_series_id) references title(id))").as_str())
-        .execute(conn)
-        .await.map_err(|e| format!("Unable to create {table_name} table -> {e}"))?;
+    execute_sql(format!("CREATE TABLE IF NOT EXISTS {table_name} (title_episode_id integer not null, title_series_id integer not null, episode_number integer, season_number integer, foreign key(title_episode_id) references {title_table}(id), foreign key(title_series_id) references {title_table}(id))").as_str(), conn)
+        .await.map_err(|e| ImportError::database(format!("Unable to create {table_name} table"), e))?;
 
     Ok(())
 }
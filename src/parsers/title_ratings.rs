@@ -1,117 +1,106 @@
-use std::{
-    fs::File,
-    io::{BufRead, BufReader, Seek},
+use crate::{
+    dataset::Table,
+    error::ImportError,
+    options::{ImportOptions, TableNames},
+    parsers::inserter::{BatchInserter, Value},
+    records::TitleRating,
+    utils::{drop_table, execute_sql},
 };
-
-use crate::{config::Args, utils::percentage_printer};
-use sqlx::{Connection, SqliteConnection};
-
-struct TitleRating {
-    title_id: u32,
-    average_rating: f32,
-    votes: u32,
-}
+use sqlx::SqliteConnection;
+use std::collections::HashSet;
 
 impl TitleRating {
-    fn from(line: String) -> Result<Self, String> {
-        let values: Vec<&str> = line.split('\t').collect();
-        let title_id = values
-            .first()
-            .and_then(|s| s.get(2..))
-            .and_then(|s| s.parse::<u32>().ok())
-            .ok_or(format!("Failed to parse title_id from {line}"))?;
-
-        let average_rating = values
-            .get(1)
-            .and_then(|s| s.parse::<f32>().ok())
-            .ok_or(format!("Failed to parse average_rating from {line}"))?;
-
-        let votes = values
-            .get(2)
-            .and_then(|s| s.parse::<u32>().ok())
-            .ok_or(format!("Failed to parse votes from {line}"))?;
-
-        Ok(Self {
-            title_id,
-            average_rating,
-            votes,
-        })
+    pub(crate) fn to_row(&self) -> Vec<Value> {
+        vec![
+            self.title_id.into(),
+            self.average_rating.into(),
+            self.votes.into(),
+        ]
     }
 }
 
-pub async fn parse_title_ratings(
-    file_name: &str,
-    table_name: &str,
-    conn: &mut SqliteConnection,
-    args: &Args,
-) -> Result<(), String> {
-    create_table(table_name, conn, args.overwrite).await?;
-    let file =
-        File::open(file_name).map_err(|e| format!("Unable to read from {file_name} -> {e}"))?;
-    let mut reader = BufReader::new(file);
-    let count = (&mut reader).lines().skip(1).count();
-    println!("-- Inserting {count} entries into {table_name} --");
+pub const COLUMNS: [&str; 3] = ["title_id", "average_rating", "votes"];
 
-    reader
-        .rewind()
-        .map_err(|e| format!("Failed to read file {file_name} after counting => {e}"))?;
+/// Inserters of title_rating, followed by its history table when ratings are snapshot.
+pub fn inserters(table_name: &str, options: &ImportOptions) -> Vec<BatchInserter> {
+    let mut inserters = vec![BatchInserter::new(table_name, 3, options)
+        .replace_by("title_id")
+        .track_vanished()];
+    if options.snapshot_ratings {
+        inserters.push(BatchInserter::new(
+            &format!("{table_name}_history"),
+            4,
+            options,
+        ));
+    }
 
-    let mut tx = conn
-        .begin()
-        .await
-        .map_err(|e| format!("Failed to start transaction => {e}"))?;
+    inserters
+}
 
-    for (i, title_rating) in reader
-        .lines()
-        .skip(1)
-        .map(|l| l.map_err(|e| format!("Unable to read line -> {e}")))
-        .map(|l| l.and_then(TitleRating::from))
-        .enumerate()
-    {
-        let query = format!("INSERT INTO {table_name} VALUES($1, $2, $3)");
-        let title_rating = title_rating?;
-        let _ = sqlx::query(&query)
-            .bind(title_rating.title_id)
-            .bind(title_rating.average_rating)
-            .bind(title_rating.votes)
-            .execute(&mut *tx)
-            .await
-            .inspect_err(|e| {
-                if args.log {
-                    eprintln!(
-                        "\nFailed to insert {}, {}, {} into {table_name} => {e}",
-                        title_rating.title_id, title_rating.average_rating, title_rating.votes,
-                    );
-                }
-            });
-        percentage_printer(i, count);
+pub fn push_rows(
+    title_rating: &TitleRating,
+    inserters: &mut [BatchInserter],
+    excluded_titles: &HashSet<u32>,
+    snapshot_date: Option<&str>,
+) {
+    if excluded_titles.contains(&title_rating.title_id) {
+        return;
     }
-    println!();
 
-    tx.commit()
-        .await
-        .map_err(|e| format!("Failed to commit transactions => {e}"))?;
+    if let Some(snapshot_date) = snapshot_date {
+        inserters[1].push(vec![
+            title_rating.title_id.into(),
+            snapshot_date.into(),
+            title_rating.average_rating.into(),
+            title_rating.votes.into(),
+        ]);
+    }
 
-    Ok(())
+    inserters[0].push(title_rating.to_row());
 }
 
-async fn create_table(
+/// Creates title_rating, along with its history table when ratings are snapshot. The history is kept when
+/// overwriting, only the rows of a snapshot taken again on the same date are replaced.
+pub async fn create_table(
     table_name: &str,
     conn: &mut SqliteConnection,
     overwrite: bool,
-) -> Result<(), String> {
+    names: &TableNames,
+    snapshot_date: Option<&str>,
+) -> Result<(), ImportError> {
+    let title_table = names.get(Table::Title);
+
     if overwrite {
-        let _ = sqlx::raw_sql(format!("DROP TABLE {table_name}").as_str())
-            .execute(&mut *conn)
-            .await;
+        drop_table(table_name, conn).await;
     }
 
-    sqlx::raw_sql(format!(
-            "CREATE TABLE IF NOT EXISTS {table_name} (title_id integer not null, average_rating real not null, votes integer not null, foreign key(title_id) references title(id))",
-        ).as_str())
+    execute_sql(format!(
+            "CREATE TABLE IF NOT EXISTS {table_name} (title_id integer not null, average_rating real not null, votes integer not null, foreign key(title_id) references {title_table}(id))",
+        ).as_str(), &mut *conn)
+        .await
+        .map_err(|e| ImportError::database(format!("Unable to create {table_name} table"), e))?;
+
+    if let Some(snapshot_date) = snapshot_date {
+        let history_table = format!("{table_name}_history");
+        execute_sql(format!(
+                "CREATE TABLE IF NOT EXISTS {history_table} (title_id integer not null, snapshot_date text not null, average_rating real not null, votes integer not null, primary key(title_id, snapshot_date), foreign key(title_id) references {title_table}(id))",
+            ).as_str(), &mut *conn)
+            .await
+            .map_err(|e| ImportError::database(format!("Unable to create {history_table} table"), e))?;
+
+        sqlx::query(&format!(
+            "DELETE FROM {history_table} WHERE snapshot_date = $1"
+        ))
+        .bind(snapshot_date)
         .execute(conn)
         .await
-        .map_err(|e| format!("Unable to create {table_name} table -> {e}"))?;
+        .map_err(|e| {
+            ImportError::database(
+                format!("Unable to clear the {snapshot_date} snapshot of {history_table}"),
+                e,
+            )
+        })?;
+    }
 
     Ok(())
 }
@@ -1,136 +1,114 @@
-use crate::{config::Args, utils::percentage_printer};
-use sqlx::{Connection, SqliteConnection};
-use std::{
-    fs::File,
-    io::{BufRead, BufReader, Seek},
+use crate::{
+    error::ImportError,
+    options::{ErrorPolicy, ImportOptions},
+    parsers::inserter::{BatchInserter, Value},
+    readers::TitleBasicsReader,
+    records::TitleBasics,
+    utils::{drop_table, execute_sql},
 };
-
-pub struct Title {
-    id: u32,
-    primary_name: String,
-    original_name: String,
-    title_type: String,
-    release_date: Option<u16>,
-    end_date: Option<u16>,
-}
-
-impl Title {
-    fn from(line: String) -> Result<Self, String> {
-        let values: Vec<&str> = line.split('\t').collect();
-        let id: u32 = values
-            .first()
-            .and_then(|s| s.get(2..))
-            .and_then(|s| s.parse().ok())
-            .ok_or(format!("Failed to parse id from {line}"))?;
-
-        let title_type = values
-            .get(1)
-            .map(|&s| s.to_string())
-            .ok_or(format!("Failed to parse title_type from {line}"))?;
-
-        let primary_name = values
-            .get(2)
-            .map(|&s| s.to_string())
-            .ok_or(format!("Failed to parse primary_name from {line}"))?;
-
-        let original_name = values
-            .get(3)
-            .map(|&s| s.to_string())
-            .ok_or(format!("Failed to parse original_name from {line}"))?;
-
-        let release_date = values.get(5).and_then(|v| v.parse::<u16>().ok());
-        let end_date = values.get(6).and_then(|v| v.parse::<u16>().ok());
-
-        Ok(Self {
-            id,
-            title_type,
-            primary_name,
-            original_name,
-            release_date,
-            end_date,
-        })
+use sqlx::SqliteConnection;
+use std::collections::HashSet;
+
+impl TitleBasics {
+    /// Values of the row in the order of `COLUMNS`, followed by the tconst when it is kept.
+    pub(crate) fn to_row(&self, imdb_ids: bool) -> Vec<Value> {
+        let mut row = vec![
+            self.id.into(),
+            self.primary_name.as_str().into(),
+            self.original_name
+                .as_deref()
+                .unwrap_or(&self.primary_name)
+                .into(),
+            self.title_type.as_str().into(),
+            self.release_date.into(),
+            self.end_date.into(),
+            self.is_adult.into(),
+            self.runtime_minutes.into(),
+        ];
+        if imdb_ids {
+            row.push(self.tconst.as_str().into());
+        }
+
+        row
     }
 }
 
-pub async fn prase_titles(
+/// Collects the ids of every title flagged as adult in title.basics.tsv, along with the number of malformed lines
+/// skipped. Like the rows, malformed lines only fail the import under the abort policy.
+pub fn adult_title_ids(
     file_name: &str,
-    table_name: &str,
-    conn: &mut SqliteConnection,
-    args: &Args,
-) -> Result<(), String> {
-    create_table(table_name, conn, args.overwrite).await?;
-    let file =
-        File::open(file_name).map_err(|e| format!("Unable to read from {file_name} -> {e}"))?;
-    let mut reader = BufReader::new(file);
-    let count = (&mut reader).lines().skip(1).count();
-    println!("-- Inserting {count} entries into {table_name} --");
+    on_error: ErrorPolicy,
+) -> Result<(HashSet<u32>, usize), ImportError> {
+    let mut ids = HashSet::new();
+    let mut skipped = 0;
+    for title in TitleBasicsReader::open(file_name)? {
+        let title = match title {
+            Ok(title) => title,
+            Err(ImportError::Parse { .. }) if on_error != ErrorPolicy::Abort => {
+                skipped += 1;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if title.is_adult {
+            ids.insert(title.id);
+        }
+    }
 
-    reader
-        .rewind()
-        .map_err(|e| format!("Failed to read file {file_name} after counting => {e}"))?;
+    Ok((ids, skipped))
+}
 
-    let mut tx = conn
-        .begin()
-        .await
-        .map_err(|e| format!("Failed to start transaction => {e}"))?;
+pub const COLUMNS: [&str; 8] = [
+    "id",
+    "primary_name",
+    "original_name",
+    "title_type",
+    "release_date",
+    "end_date",
+    "is_adult",
+    "runtime_minutes",
+];
+
+pub fn inserters(table_name: &str, options: &ImportOptions) -> Vec<BatchInserter> {
+    let mut columns = COLUMNS.to_vec();
+    if options.imdb_ids {
+        columns.push("tconst");
+    }
 
-    for (i, title) in reader
-        .lines()
-        .skip(1)
-        .map(|l| l.map_err(|e| format!("Unable to read line -> {e}")))
-        .map(|l| l.and_then(Title::from))
-        .enumerate()
-    {
-        let title = title?;
-        let query = format!("INSERT INTO {table_name} VALUES($1, $2, $3, $4, $5, $6)");
-        let _ = sqlx::query(&query)
-            .bind(title.id)
-            .bind(&title.primary_name)
-            .bind(&title.original_name)
-            .bind(&title.title_type)
-            .bind(title.release_date)
-            .bind(title.end_date)
-            .execute(&mut *tx)
-            .await
-            .map_err(|e| {
-                if args.log {
-                    eprintln!(
-                        "\nFailed to insert {}, {}, {}, {}, {:?}, {:?} into {table_name} => {e}",
-                        title.id,
-                        title.primary_name,
-                        title.original_name,
-                        title.title_type,
-                        title.release_date,
-                        title.end_date
-                    );
-                }
-            });
+    vec![BatchInserter::new(table_name, columns.len(), options).upsert(&columns)]
+}
 
-        percentage_printer(i, count);
+pub fn push_rows(
+    title: &TitleBasics,
+    inserters: &mut [BatchInserter],
+    excluded_titles: &HashSet<u32>,
+    imdb_ids: bool,
+) {
+    if excluded_titles.contains(&title.id) {
+        return;
     }
-    println!();
-
-    tx.commit()
-        .await
-        .map_err(|e| format!("Failed to commit transactions => {e}"))?;
 
-    Ok(())
+    inserters[0].push(title.to_row(imdb_ids));
 }
 
-async fn create_table(
+pub async fn create_table(
     table_name: &str,
     conn: &mut SqliteConnection,
     overwrite: bool,
-) -> Result<(), String> {
+    imdb_ids: bool,
+) -> Result<(), ImportError> {
     if overwrite {
-        let _ = sqlx::raw_sql(format!("DROP TABLE {table_name}").as_str())
-            .execute(&mut *conn)
-            .await;
+        drop_table(table_name, conn).await;
     }
 
-    sqlx::raw_sql(format!("CREATE TABLE IF NOT EXISTS {table_name} (id integer primary key, primary_name text not null, original_name text not null, title_type text not null, release_date integer, end_date integer)").as_str())
-        .execute(conn)
-        .await.map_err(|e| format!("Unable to create {table_name} table -> {e}"))?;
+    let tconst_column = if imdb_ids {
+        ", tconst text not null unique"
+    } else {
+        ""
+    };
+
+    execute_sql(format!("CREATE TABLE IF NOT EXISTS {table_name} (id integer primary key, primary_name text not null, original_name text not null, title_type text not null, release_date integer, end_date integer, is_adult boolean not null, runtime_minutes integer{tconst_column})").as_str(), conn)
+        .await.map_err(|e| ImportError::database(format!("Unable to create {table_name} table"), e))?;
 
     Ok(())
 }
@@ -0,0 +1,84 @@
+use crate::{error::ImportError, options::ImportOptions, utils::execute_sql};
+use sqlx::SqliteConnection;
+
+/// Trades durability for speed, a crash during the import can leave the database corrupted.
+/// The journal is kept in memory rather than turned off, as a batch that fails on a foreign key
+/// has to be rolled back before its rows are retried one by one.
+const FAST_PRAGMAS: &str = "PRAGMA locking_mode = EXCLUSIVE;
+PRAGMA journal_mode = MEMORY;
+PRAGMA synchronous = OFF;
+PRAGMA cache_size = -1048576;
+PRAGMA temp_store = MEMORY;";
+
+/// Keeps the database consistent on a crash while still avoiding most of the syncing.
+const SAFE_PRAGMAS: &str = "PRAGMA journal_mode = WAL;
+PRAGMA synchronous = NORMAL;
+PRAGMA cache_size = -1048576;
+PRAGMA temp_store = MEMORY;";
+
+/// SQLite defaults, restored once the import is done so the database is safe to use by other programs.
+const RESTORE_PRAGMAS: &str = "PRAGMA locking_mode = NORMAL;
+PRAGMA journal_mode = DELETE;
+PRAGMA synchronous = FULL;
+PRAGMA temp_store = DEFAULT;";
+
+/// Applies the bulk import profile selected through `--fast` or `--safe`.
+pub async fn begin_import(
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+) -> Result<(), ImportError> {
+    let pragmas = if options.fast {
+        FAST_PRAGMAS
+    } else if options.safe {
+        SAFE_PRAGMAS
+    } else {
+        return Ok(());
+    };
+
+    execute_sql(pragmas, conn)
+        .await
+        .map_err(|e| ImportError::database("Failed to apply import pragmas", e))?;
+
+    Ok(())
+}
+
+/// Restores the safe settings after an import profile was applied, then refreshes the query planner
+/// statistics and optionally compacts the database file.
+pub async fn finish_import(
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+) -> Result<(), ImportError> {
+    if options.fast || options.safe {
+        restore_pragmas(conn, options).await?;
+
+        options.print_step("-- Analyzing tables --");
+        execute_sql("ANALYZE", &mut *conn)
+            .await
+            .map_err(|e| ImportError::database("Failed to analyze tables", e))?;
+    }
+
+    if options.vacuum {
+        options.print_step("-- Vacuuming database --");
+        execute_sql("VACUUM", conn)
+            .await
+            .map_err(|e| ImportError::database("Failed to vacuum database", e))?;
+    }
+
+    Ok(())
+}
+
+/// Restores the SQLite defaults after an import profile was applied, also when the import failed halfway.
+pub async fn restore_pragmas(
+    conn: &mut SqliteConnection,
+    options: &ImportOptions,
+) -> Result<(), ImportError> {
+    if !options.fast && !options.safe {
+        return Ok(());
+    }
+
+    execute_sql(RESTORE_PRAGMAS, conn)
+        .await
+        .map_err(|e| ImportError::database("Failed to restore pragmas", e))?;
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use crate::{
+    error::ImportError,
+    records::{
+        NameBasics, Record, TitleAka, TitleBasics, TitleCrew, TitleEpisode, TitlePrincipal,
+        TitleRating,
+    },
+    tsv::{read_header, DatasetHeader},
+    utils::open_dataset,
+};
+use std::{
+    io::{BufRead, Lines},
+    marker::PhantomData,
+};
+
+/// Streams the records of a dataset file, one line at a time, without going through the database.
+/// Gzip archives are decompressed on the fly, and the values are read from the columns named by the header line.
+///
+/// ```no_run
+/// use imdbsql::TitleBasicsReader;
+///
+/// # fn run() -> Result<(), imdbsql::ImportError> {
+/// for title in TitleBasicsReader::open("title.basics.tsv.gz")? {
+///     let title = title?;
+///     println!("{} {}", title.tconst, title.primary_name);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct DatasetReader<T> {
+    file_name: String,
+    header: DatasetHeader,
+    lines: Lines<Box<dyn BufRead + Send>>,
+    line_number: usize,
+    record: PhantomData<T>,
+}
+
+pub type TitleBasicsReader = DatasetReader<TitleBasics>;
+pub type NameBasicsReader = DatasetReader<NameBasics>;
+pub type TitleAkasReader = DatasetReader<TitleAka>;
+pub type TitleRatingsReader = DatasetReader<TitleRating>;
+pub type TitleCrewReader = DatasetReader<TitleCrew>;
+pub type TitleEpisodeReader = DatasetReader<TitleEpisode>;
+pub type TitlePrincipalsReader = DatasetReader<TitlePrincipal>;
+
+impl<T: Record> DatasetReader<T> {
+    /// Opens the dataset file, either unzipped (.tsv) or as the original gzip archive (.tsv.gz).
+    pub fn open(file_name: &str) -> Result<Self, ImportError> {
+        let mut lines = open_dataset(file_name)?.lines();
+        let header = read_header(&mut lines, T::DATASET, file_name)?;
+
+        Ok(Self {
+            file_name: file_name.to_string(),
+            header,
+            lines,
+            line_number: 1,
+            record: PhantomData,
+        })
+    }
+
+    /// Columns named by the header line of the file.
+    pub fn header(&self) -> &DatasetHeader {
+        &self.header
+    }
+
+    /// Line number of the last record read, the header being the first line.
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+}
+
+impl<T: Record> Iterator for DatasetReader<T> {
+    type Item = Result<T, ImportError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?;
+        self.line_number += 1;
+
+        Some(match line {
+            Ok(line) => T::parse_line(&self.header, &line)
+                .map_err(|e| e.at(&self.file_name, self.line_number)),
+            Err(e) => Err(ImportError::io(
+                format!(
+                    "Unable to read line {} of {}",
+                    self.line_number, self.file_name
+                ),
+                e,
+            )),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TitleBasicsReader;
+    use crate::{dataset::Dataset, fixtures::Datasets};
+    use std::thread;
+
+    #[test]
+    fn reads_inside_spawned_threads() {
+        let datasets = Datasets::new("reads_inside_spawned_threads");
+        datasets.write(
+            Dataset::TitleBasics,
+            &["tt0000001\tshort\tCarmencita\tCarmencita\t0\t1894\t\\N\t1\tDocumentary"],
+        );
+        let reader =
+            TitleBasicsReader::open(&datasets.options().dataset_path(Dataset::TitleBasics))
+                .unwrap();
+
+        let titles = thread::spawn(move || {
+            reader
+                .map(|title| title.unwrap().primary_name)
+                .collect::<Vec<_>>()
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(titles, vec!["Carmencita"]);
+    }
+}
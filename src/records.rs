@@ -0,0 +1,386 @@
+use crate::{
+    dataset::Dataset,
+    error::ParseError,
+    tsv::{DatasetHeader, Fields},
+    utils::find_strings,
+};
+use std::str::FromStr;
+
+/// A line of an IMDb dataset file, parsed into its typed values.
+///
+/// The identifiers are stored without their tt and nm prefixes, as the tables store them.
+pub trait Record: Sized {
+    /// Dataset file the records are read from.
+    const DATASET: Dataset;
+
+    /// Parses a line of the dataset file, without its trailing newline, its values being laid out as named by
+    /// the header. `\N` values are parsed as none, or as empty lists.
+    fn parse_line(header: &DatasetHeader, line: &str) -> Result<Self, ParseError>;
+
+    /// Parses a line whose values are in the documented order of the dataset columns.
+    fn from_line(line: &str) -> Result<Self, ParseError> {
+        Self::parse_line(&DatasetHeader::new(Self::DATASET), line)
+    }
+}
+
+/// A line of title.basics.tsv.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TitleBasics {
+    pub id: u32,
+    pub tconst: String,
+    pub title_type: String,
+    pub primary_name: String,
+    /// Missing from a few titles, the title table stores the primary name instead.
+    pub original_name: Option<String>,
+    pub is_adult: bool,
+    pub release_date: Option<u16>,
+    pub end_date: Option<u16>,
+    pub runtime_minutes: Option<u32>,
+    pub genres: Vec<String>,
+}
+
+impl Record for TitleBasics {
+    const DATASET: Dataset = Dataset::TitleBasics;
+
+    fn parse_line(header: &DatasetHeader, line: &str) -> Result<Self, ParseError> {
+        let values = Fields::decode(line, header)?;
+        Ok(Self {
+            id: id(&values, 1, "tconst")?,
+            tconst: value(&values, 1, "tconst")?.to_string(),
+            title_type: value(&values, 2, "title_type")?.to_string(),
+            primary_name: value(&values, 3, "primary_name")?.to_string(),
+            original_name: optional_text(&values, 4),
+            is_adult: value(&values, 5, "is_adult")? == "1",
+            release_date: optional_number(&values, 6, "release_date")?,
+            end_date: optional_number(&values, 7, "end_date")?,
+            runtime_minutes: optional_number(&values, 8, "runtime_minutes")?,
+            genres: split_list(&values, 9),
+        })
+    }
+}
+
+/// A line of name.basics.tsv.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameBasics {
+    pub id: u32,
+    pub nconst: String,
+    pub name: String,
+    pub birth_date: Option<u16>,
+    pub death_date: Option<u16>,
+    pub professions: Vec<String>,
+    /// Titles the name is known for.
+    pub title_ids: Vec<u32>,
+}
+
+impl Record for NameBasics {
+    const DATASET: Dataset = Dataset::NameBasics;
+
+    fn parse_line(header: &DatasetHeader, line: &str) -> Result<Self, ParseError> {
+        let values = Fields::decode(line, header)?;
+        Ok(Self {
+            id: id(&values, 1, "nconst")?,
+            nconst: value(&values, 1, "nconst")?.to_string(),
+            name: value(&values, 2, "name")?.to_string(),
+            birth_date: optional_number(&values, 3, "birth_date")?,
+            death_date: optional_number(&values, 4, "death_date")?,
+            professions: split_list(&values, 5),
+            title_ids: split_ids(&values, 6),
+        })
+    }
+}
+
+/// A line of title.akas.tsv.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TitleAka {
+    pub title_id: u32,
+    pub ordering: u32,
+    pub title: String,
+    pub region: Option<String>,
+    pub language: Option<String>,
+    pub types: Vec<String>,
+    pub attributes: Vec<String>,
+    pub is_original_title: bool,
+}
+
+impl Record for TitleAka {
+    const DATASET: Dataset = Dataset::TitleAkas;
+
+    fn parse_line(header: &DatasetHeader, line: &str) -> Result<Self, ParseError> {
+        let values = Fields::decode(line, header)?;
+        Ok(Self {
+            title_id: id(&values, 1, "title_id")?,
+            ordering: number(&values, 2, "ordering")?,
+            title: value(&values, 3, "title")?.to_string(),
+            region: optional_text(&values, 4),
+            language: optional_text(&values, 5),
+            types: split_array(&values, 6),
+            attributes: split_array(&values, 7),
+            is_original_title: value(&values, 8, "is_original_title")? == "1",
+        })
+    }
+}
+
+/// A line of title.ratings.tsv.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TitleRating {
+    pub title_id: u32,
+    pub average_rating: f32,
+    pub votes: u32,
+}
+
+impl Record for TitleRating {
+    const DATASET: Dataset = Dataset::TitleRatings;
+
+    fn parse_line(header: &DatasetHeader, line: &str) -> Result<Self, ParseError> {
+        let values = Fields::decode(line, header)?;
+        Ok(Self {
+            title_id: id(&values, 1, "title_id")?,
+            average_rating: number(&values, 2, "average_rating")?,
+            votes: number(&values, 3, "votes")?,
+        })
+    }
+}
+
+/// A line of title.crew.tsv.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TitleCrew {
+    pub title_id: u32,
+    pub director_ids: Vec<u32>,
+    pub writer_ids: Vec<u32>,
+}
+
+impl Record for TitleCrew {
+    const DATASET: Dataset = Dataset::TitleCrew;
+
+    fn parse_line(header: &DatasetHeader, line: &str) -> Result<Self, ParseError> {
+        let values = Fields::decode(line, header)?;
+        Ok(Self {
+            title_id: id(&values, 1, "title_id")?,
+            director_ids: split_ids(&values, 2),
+            writer_ids: split_ids(&values, 3),
+        })
+    }
+}
+
+/// A line of title.episode.tsv.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TitleEpisode {
+    pub title_episode_id: u32,
+    pub title_series_id: u32,
+    pub season_number: Option<u32>,
+    pub episode_number: Option<u32>,
+}
+
+impl Record for TitleEpisode {
+    const DATASET: Dataset = Dataset::TitleEpisode;
+
+    fn parse_line(header: &DatasetHeader, line: &str) -> Result<Self, ParseError> {
+        let values = Fields::decode(line, header)?;
+        Ok(Self {
+            title_episode_id: id(&values, 1, "title_episode_id")?,
+            title_series_id: id(&values, 2, "title_series_id")?,
+            season_number: optional_number(&values, 3, "season_number")?,
+            episode_number: optional_number(&values, 4, "episode_number")?,
+        })
+    }
+}
+
+/// A line of title.principals.tsv.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TitlePrincipal {
+    pub title_id: u32,
+    pub ordering: u32,
+    pub name_id: u32,
+    pub category: String,
+    pub job: Option<String>,
+    pub characters: Vec<String>,
+}
+
+impl Record for TitlePrincipal {
+    const DATASET: Dataset = Dataset::TitlePrincipals;
+
+    fn parse_line(header: &DatasetHeader, line: &str) -> Result<Self, ParseError> {
+        let values = Fields::decode(line, header)?;
+        Ok(Self {
+            title_id: id(&values, 1, "title_id")?,
+            ordering: number(&values, 2, "ordering")?,
+            name_id: id(&values, 3, "name_id")?,
+            category: value(&values, 4, "category")?.to_string(),
+            job: optional_text(&values, 5),
+            characters: values.get(6).map(find_strings).unwrap_or_default(),
+        })
+    }
+}
+
+/// Identifier of the first column of a line, without its tt or nm prefix, none when it can not be read.
+/// Malformed lines still identify the row they were meant to update when their identifier is readable.
+pub(crate) fn line_id(header: &DatasetHeader, line: &str) -> Option<u32> {
+    header.key(line)?.get(2..)?.parse().ok()
+}
+
+/// Value of the column, the columns being numbered from 1.
+fn value<'a>(values: &'a Fields, column: usize, name: &str) -> Result<&'a str, ParseError> {
+    values
+        .get(column)
+        .ok_or_else(|| ParseError::new(values.column(column), format!("Missing {name}")))
+}
+
+/// Identifier of the column, without its tt or nm prefix.
+fn id(values: &Fields, column: usize, name: &str) -> Result<u32, ParseError> {
+    let value = value(values, column, name)?;
+    value
+        .get(2..)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| ParseError::new(values.column(column), format!("Invalid {name} {value}")))
+}
+
+fn number<T: FromStr>(values: &Fields, column: usize, name: &str) -> Result<T, ParseError> {
+    let value = value(values, column, name)?;
+    value
+        .parse()
+        .map_err(|_| ParseError::new(values.column(column), format!("Invalid {name} {value}")))
+}
+
+/// Number of the column, none when the column is `\N`.
+fn optional_number<T: FromStr>(
+    values: &Fields,
+    column: usize,
+    name: &str,
+) -> Result<Option<T>, ParseError> {
+    match values.get(column) {
+        Some(_) => number(values, column, name).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn optional_text(values: &Fields, column: usize) -> Option<String> {
+    values.get(column).map(|s| s.to_string())
+}
+
+/// Values of a comma separated list.
+fn split_list(values: &Fields, column: usize) -> Vec<String> {
+    values
+        .get(column)
+        .map(|s| s.split(',').map(|v| v.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Identifiers of a comma separated list, without their tt or nm prefixes.
+fn split_ids(values: &Fields, column: usize) -> Vec<u32> {
+    values
+        .get(column)
+        .map(|s| {
+            s.split(',')
+                .filter_map(|v| v.get(2..))
+                .filter_map(|s| s.parse::<u32>().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Array columns in title.akas.tsv are comma separated, although some rows join their values with `\x02` instead.
+fn split_array(values: &Fields, column: usize) -> Vec<String> {
+    values
+        .get(column)
+        .map(|s| {
+            s.split(['\u{2}', ','])
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NameBasics, Record, TitleAka, TitleBasics, TitlePrincipal, TitleRating};
+    use crate::{dataset::Dataset, tsv::DatasetHeader};
+
+    #[test]
+    fn parses_title_basics() {
+        let title = TitleBasics::from_line(
+            "tt0000009\tmovie\tMiss Jerry\tMiss Jerry\t0\t1894\t\\N\t45\tRomance,Drama",
+        )
+        .unwrap();
+
+        assert_eq!(title.id, 9);
+        assert_eq!(title.tconst, "tt0000009");
+        assert_eq!(title.release_date, Some(1894));
+        assert_eq!(title.end_date, None);
+        assert_eq!(title.runtime_minutes, Some(45));
+        assert_eq!(title.genres, vec!["Romance", "Drama"]);
+    }
+
+    #[test]
+    fn parses_name_basics() {
+        let name = NameBasics::from_line(
+            "nm0000001\tFred Astaire\t1899\t1987\tactor,miscellaneous\ttt0050419,tt0053137",
+        )
+        .unwrap();
+
+        assert_eq!(name.id, 1);
+        assert_eq!(name.death_date, Some(1987));
+        assert_eq!(name.professions, vec!["actor", "miscellaneous"]);
+        assert_eq!(name.title_ids, vec![50419, 53137]);
+    }
+
+    #[test]
+    fn parses_title_principal() {
+        let principal =
+            TitlePrincipal::from_line("tt0000001\t2\tnm0005690\tdirector\t\\N\t[\"Herself\"]")
+                .unwrap();
+
+        assert_eq!(principal.name_id, 5690);
+        assert_eq!(principal.job, None);
+        assert_eq!(principal.characters, vec!["Herself"]);
+    }
+
+    #[test]
+    fn parses_title_aka() {
+        let aka = TitleAka::from_line(
+            "tt0000001\t1\tCarmencita\t\\N\t\\N\toriginal\u{2}imdbDisplay\t\\N\t1",
+        )
+        .unwrap();
+
+        assert_eq!(aka.region, None);
+        assert_eq!(aka.types, vec!["original", "imdbDisplay"]);
+        assert!(aka.attributes.is_empty());
+        assert!(aka.is_original_title);
+
+        let aka = TitleAka::from_line(
+            "tt0000001\t2\tKarmensita\tRU\t\\N\t\\N\tliteral title, short title\t0",
+        )
+        .unwrap();
+
+        assert_eq!(aka.region.as_deref(), Some("RU"));
+        assert!(aka.types.is_empty());
+        assert_eq!(aka.attributes, vec!["literal title", "short title"]);
+        assert!(!aka.is_original_title);
+    }
+
+    #[test]
+    fn parses_columns_named_by_header() {
+        let header = DatasetHeader::parse(
+            Dataset::TitleRatings,
+            "numVotes\ttconst\tsource\taverageRating",
+        )
+        .unwrap();
+        let rating = TitleRating::parse_line(&header, "2104\ttt0000001\timdb\t5.7").unwrap();
+        assert_eq!(rating.title_id, 1);
+        assert_eq!(rating.votes, 2104);
+
+        let error = TitleRating::parse_line(&header, "many\ttt0000001\timdb\t5.7").unwrap_err();
+        assert_eq!(error.column, 1);
+    }
+
+    #[test]
+    fn locates_parse_errors() {
+        let error = TitleBasics::from_line("tt0000009\tmovie").unwrap_err();
+        assert_eq!(error.column, 3);
+
+        let error =
+            TitlePrincipal::from_line("tt0000001\tfirst\tnm0005690\tself\t\\N\t\\N").unwrap_err();
+        assert_eq!(error.column, 2);
+        assert_eq!(error.message, "Invalid ordering first");
+    }
+}
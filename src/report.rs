@@ -0,0 +1,162 @@
+use crate::{dataset::Table, error::ImportError, parsers::rejects::SkipReason};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+/// Outcome of importing a table. Tables derived from the same dataset are parsed in a single pass,
+/// so they share the lines read and the duration of that pass.
+pub struct TableReport {
+    pub table: Table,
+    pub table_name: String,
+    pub lines_read: usize,
+    pub rows_inserted: usize,
+    /// Lines and rows skipped, indexed by `SkipReason`, see `TableReport::skipped_for`.
+    pub skipped: [usize; SkipReason::ALL.len()],
+    pub duration: Duration,
+    pub error: Option<ImportError>,
+}
+
+impl TableReport {
+    /// Report of a table that failed before its dataset was read.
+    pub(crate) fn failed(
+        table: Table,
+        table_name: String,
+        duration: Duration,
+        error: ImportError,
+    ) -> Self {
+        Self {
+            table,
+            table_name,
+            lines_read: 0,
+            rows_inserted: 0,
+            skipped: [0; SkipReason::ALL.len()],
+            duration,
+            error: Some(error),
+        }
+    }
+
+    /// Lines and rows skipped for the reason.
+    pub fn skipped_for(&self, reason: SkipReason) -> usize {
+        self.skipped[reason as usize]
+    }
+
+    pub fn rows_per_second(&self) -> f64 {
+        let seconds = self.duration.as_secs_f64();
+        if seconds > 0.0 {
+            self.rows_inserted as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match self.error {
+            Some(_) => "failed",
+            None => "ok",
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let skipped: Map<String, Value> = SkipReason::ALL
+            .iter()
+            .map(|&reason| (reason.name().to_string(), self.skipped_for(reason).into()))
+            .collect();
+
+        json!({
+            "table": self.table_name,
+            "status": self.status(),
+            "error": self.error.as_ref().map(ImportError::to_string),
+            "lines_read": self.lines_read,
+            "rows_inserted": self.rows_inserted,
+            "skipped": skipped,
+            "duration_seconds": self.duration.as_secs_f64(),
+            "rows_per_second": self.rows_per_second(),
+        })
+    }
+}
+
+/// How an import went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportStatus {
+    Ok,
+    /// At least one of the tables failed.
+    Failed,
+    /// The import stopped at the first error, following `ErrorPolicy::Abort`.
+    Aborted,
+}
+
+impl ImportStatus {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ImportStatus::Ok => "ok",
+            ImportStatus::Failed => "failed",
+            ImportStatus::Aborted => "aborted",
+        }
+    }
+}
+
+/// Outcome of an import, with the report of every table inserted into.
+pub struct ImportReport {
+    pub status: ImportStatus,
+    pub tables: Vec<TableReport>,
+    pub duration: Duration,
+}
+
+impl ImportReport {
+    /// Names of the tables that failed.
+    pub fn failed_tables(&self) -> Vec<&str> {
+        self.tables
+            .iter()
+            .filter(|report| report.error.is_some())
+            .map(|report| report.table_name.as_str())
+            .collect()
+    }
+
+    /// Error of the first table that failed, the command line exits with its code.
+    pub fn error(&self) -> Option<&ImportError> {
+        self.tables.iter().find_map(|report| report.error.as_ref())
+    }
+
+    /// Prints the per table statistics of the import, the errors of the failed tables being printed as they happen.
+    pub fn print(&self) {
+        let reasons: Vec<&str> = SkipReason::ALL.iter().map(|r| r.name()).collect();
+        println!(
+            "\n{:<20} {:>10} {:>10} {} {:>9} {:>10} status",
+            "table",
+            "lines",
+            "inserted",
+            reasons
+                .iter()
+                .map(|r| format!("{r:>19}"))
+                .collect::<Vec<String>>()
+                .join(" "),
+            "seconds",
+            "rows/sec"
+        );
+
+        for report in &self.tables {
+            let skipped: Vec<String> = report.skipped.iter().map(|s| format!("{s:>19}")).collect();
+            println!(
+                "{:<20} {:>10} {:>10} {} {:>9.2} {:>10.0} {}",
+                report.table_name,
+                report.lines_read,
+                report.rows_inserted,
+                skipped.join(" "),
+                report.duration.as_secs_f64(),
+                report.rows_per_second(),
+                report.status()
+            );
+        }
+    }
+
+    /// The report as pretty printed JSON.
+    pub fn to_json(&self) -> String {
+        let tables: Vec<Value> = self.tables.iter().map(TableReport::to_json).collect();
+        let report = json!({
+            "status": self.status.name(),
+            "duration_seconds": self.duration.as_secs_f64(),
+            "tables": tables,
+        });
+
+        serde_json::to_string_pretty(&report).expect("the report only holds serializable values")
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{
+    dataset::Dataset,
+    error::{ImportError, ParseError},
+};
+use std::{borrow::Cow, io};
+
+/// Columns of a dataset file as named by its header line, mapping the columns of the dataset to their
+/// position in the lines. The columns can come in any order and extra columns are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DatasetHeader {
+    dataset: Dataset,
+    /// Position of every column of `Dataset::columns` in the lines.
+    positions: Vec<usize>,
+    /// Number of values of every line, the extra columns included.
+    len: usize,
+}
+
+impl DatasetHeader {
+    /// Header listing the columns of the dataset in their documented order.
+    pub fn new(dataset: Dataset) -> Self {
+        let len = dataset.columns().len();
+        Self {
+            dataset,
+            positions: (0..len).collect(),
+            len,
+        }
+    }
+
+    /// Maps the columns named by the header line, failing when a column of the dataset is missing.
+    pub fn parse(dataset: Dataset, line: &str) -> Result<Self, ParseError> {
+        let names: Vec<&str> = line.split('\t').collect();
+        let positions = dataset
+            .columns()
+            .iter()
+            .map(|column| {
+                names.iter().position(|name| name == column).ok_or_else(|| {
+                    ParseError::new(
+                        names.len() + 1,
+                        format!("Missing column {column} in the header"),
+                    )
+                })
+            })
+            .collect::<Result<Vec<usize>, ParseError>>()?;
+
+        Ok(Self {
+            dataset,
+            positions,
+            len: names.len(),
+        })
+    }
+
+    pub fn dataset(&self) -> Dataset {
+        self.dataset
+    }
+
+    /// Value of the first column of the dataset, read without decoding the line so it can be read from lines
+    /// that are otherwise malformed.
+    pub(crate) fn key<'a>(&self, line: &'a str) -> Option<&'a str> {
+        line.split('\t')
+            .nth(self.positions[0])
+            .filter(|value| *value != "\\N")
+    }
+}
+
+/// Values of a dataset line, split on its tabs. `\N` is decoded as none and the backslash escapes of the
+/// other values are unescaped.
+pub(crate) struct Fields<'a> {
+    values: Vec<Option<Cow<'a, str>>>,
+    header: &'a DatasetHeader,
+}
+
+impl<'a> Fields<'a> {
+    /// Decodes a line of the dataset, which has to hold a value for every column of its header.
+    pub fn decode(line: &'a str, header: &'a DatasetHeader) -> Result<Self, ParseError> {
+        let values: Vec<Option<Cow<str>>> = line.split('\t').map(decode_value).collect();
+        check_count(values.len(), header.len)?;
+
+        Ok(Self { values, header })
+    }
+
+    /// Value of a column of the dataset, numbered from 1 in the order of `Dataset::columns`.
+    /// None when the value is `\N`.
+    pub fn get(&self, column: usize) -> Option<&str> {
+        self.values[self.header.positions[column - 1]].as_deref()
+    }
+
+    /// Column of the line holding a column of the dataset, both being numbered from 1.
+    pub fn column(&self, column: usize) -> usize {
+        self.header.positions[column - 1] + 1
+    }
+}
+
+/// Reads the header line of a dataset file, failing when it misses a column of the dataset.
+pub(crate) fn read_header(
+    lines: &mut impl Iterator<Item = io::Result<String>>,
+    dataset: Dataset,
+    file_name: &str,
+) -> Result<DatasetHeader, ImportError> {
+    let header = match lines.next() {
+        Some(line) => line
+            .map_err(|e| ImportError::io(format!("Unable to read the header of {file_name}"), e))?,
+        None => return Err(ParseError::new(1, "Missing header").at(file_name, 1)),
+    };
+
+    DatasetHeader::parse(dataset, &header).map_err(|e| e.at(file_name, 1))
+}
+
+/// Fails at the first missing or extra column.
+fn check_count(found: usize, expected: usize) -> Result<(), ParseError> {
+    if found != expected {
+        return Err(ParseError::new(
+            found.min(expected) + 1,
+            format!("Expected {expected} columns, found {found}"),
+        ));
+    }
+
+    Ok(())
+}
+
+fn decode_value(value: &str) -> Option<Cow<'_, str>> {
+    if value == "\\N" {
+        None
+    } else if value.contains('\\') {
+        Some(Cow::Owned(unescape(value)))
+    } else {
+        Some(Cow::Borrowed(value))
+    }
+}
+
+/// Unescapes the tabs, newlines and backslashes of a value. Other sequences, such as the escaped quotes
+/// of the characters, are kept as they are.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DatasetHeader, Fields};
+    use crate::dataset::Dataset;
+
+    #[test]
+    fn decodes_values() {
+        let header = DatasetHeader::new(Dataset::TitleCrew);
+        let fields = Fields::decode("tt0000001\t\\N\tTab\\tand \\\\ slash", &header).unwrap();
+
+        assert_eq!(fields.get(1), Some("tt0000001"));
+        assert_eq!(fields.get(2), None);
+        assert_eq!(fields.get(3), Some("Tab\tand \\ slash"));
+    }
+
+    #[test]
+    fn keeps_other_escapes() {
+        let header = DatasetHeader::new(Dataset::TitleCrew);
+        let fields = Fields::decode("tt0000001\t\\\"a\\\"\t\\", &header).unwrap();
+
+        assert_eq!(fields.get(2), Some("\\\"a\\\""));
+        assert_eq!(fields.get(3), Some("\\"));
+    }
+
+    #[test]
+    fn reports_column_count() {
+        let header = DatasetHeader::new(Dataset::TitleCrew);
+        let error = Fields::decode("tt0000001\t\\N", &header).err().unwrap();
+        assert_eq!(error.column, 3);
+        assert_eq!(error.message, "Expected 3 columns, found 2");
+
+        let error = Fields::decode("tt0000001\t\\N\t\\N\textra", &header)
+            .err()
+            .unwrap();
+        assert_eq!(error.column, 4);
+    }
+
+    #[test]
+    fn maps_columns_by_name() {
+        let header =
+            DatasetHeader::parse(Dataset::TitleCrew, "writers\tsource\ttconst\tdirectors").unwrap();
+        let fields = Fields::decode("nm0000002\timdb\ttt0000001\tnm0000001", &header).unwrap();
+
+        assert_eq!(fields.get(1), Some("tt0000001"));
+        assert_eq!(fields.get(2), Some("nm0000001"));
+        assert_eq!(fields.get(3), Some("nm0000002"));
+        assert_eq!(fields.column(3), 1);
+    }
+
+    #[test]
+    fn requires_columns() {
+        let error = DatasetHeader::parse(Dataset::TitleCrew, "tconst\twriters").unwrap_err();
+        assert_eq!(error.column, 3);
+        assert_eq!(error.message, "Missing column directors in the header");
+    }
+}
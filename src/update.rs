@@ -0,0 +1,139 @@
+use crate::{error::ImportError, utils::execute_sql};
+use sqlx::SqliteConnection;
+use std::{future::Future, pin::Pin};
+
+/// Temporary table holding the ids found in the dataset, of the core table rows or of the parents of the joining table rows.
+pub fn keys_table(table_name: &str) -> String {
+    format!("temp.{table_name}_keys")
+}
+
+/// Starts recording the ids of the table found in the dataset, so the rows missing from it can be found afterwards.
+pub async fn track_keys(table_name: &str, conn: &mut SqliteConnection) -> Result<(), ImportError> {
+    let keys_table = keys_table(table_name);
+    execute_sql(
+        format!(
+            "DROP TABLE IF EXISTS {keys_table}; CREATE TABLE {keys_table} (id integer primary key)"
+        )
+        .as_str(),
+        conn,
+    )
+    .await
+    .map_err(|e| ImportError::database(format!("Unable to create {keys_table} table"), e))?;
+
+    Ok(())
+}
+
+/// Records the ids found in the dataset into the keys table of the table.
+pub async fn record_keys(
+    table_name: &str,
+    keys: &[String],
+    conn: &mut SqliteConnection,
+) -> Result<(), ImportError> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+
+    let keys_table = keys_table(table_name);
+    let values: Vec<String> = keys.iter().map(|key| format!("({key})")).collect();
+    execute_sql(
+        format!(
+            "INSERT OR IGNORE INTO {keys_table} VALUES {}",
+            values.join(", ")
+        )
+        .as_str(),
+        conn,
+    )
+    .await
+    .map_err(|e| ImportError::database(format!("Unable to record keys into {keys_table}"), e))?;
+
+    Ok(())
+}
+
+/// Deletes the rows whose key column holds an id that was not found in the dataset, along with every row referencing
+/// them, returning how many rows were deleted.
+pub async fn delete_vanished(
+    table_name: &str,
+    key: &str,
+    dataset_tables: &[String],
+    conn: &mut SqliteConnection,
+) -> Result<u64, ImportError> {
+    let condition = format!("{key} NOT IN (SELECT id FROM {})", keys_table(table_name));
+    delete_cascading(table_name, &condition, dataset_tables, conn).await
+}
+
+/// Deletes the rows of the table matching the condition. SQLite refuses to delete rows that are still referenced,
+/// so the rows of every dataset table referencing them through a foreign key are deleted first. The rows of the
+/// other tables of the database are left alone, the deletion then failing on their foreign keys.
+pub fn delete_cascading<'a>(
+    table_name: &'a str,
+    condition: &'a str,
+    dataset_tables: &'a [String],
+    conn: &'a mut SqliteConnection,
+) -> Pin<Box<dyn Future<Output = Result<u64, ImportError>> + Send + 'a>> {
+    Box::pin(async move {
+        for foreign_key in referencing_tables(table_name, &mut *conn)
+            .await?
+            .into_iter()
+            .filter(|foreign_key| dataset_tables.contains(&foreign_key.table))
+        {
+            let (from, to): (Vec<String>, Vec<String>) = foreign_key.columns.into_iter().unzip();
+            let child_condition = format!(
+                "({}) IN (SELECT {} FROM {table_name} WHERE {condition})",
+                from.join(", "),
+                to.join(", ")
+            );
+            delete_cascading(
+                &foreign_key.table,
+                &child_condition,
+                dataset_tables,
+                &mut *conn,
+            )
+            .await?;
+        }
+
+        let result = execute_sql(
+            format!("DELETE FROM {table_name} WHERE {condition}").as_str(),
+            &mut *conn,
+        )
+        .await
+        .map_err(|e| ImportError::database(format!("Unable to delete rows of {table_name}"), e))?;
+
+        Ok(result.rows_affected())
+    })
+}
+
+/// Foreign key of a table referencing another, as pairs of (from, to) columns.
+struct ForeignKey {
+    table: String,
+    id: i64,
+    columns: Vec<(String, String)>,
+}
+
+/// Foreign keys of every table referencing the given table.
+async fn referencing_tables(
+    table_name: &str,
+    conn: &mut SqliteConnection,
+) -> Result<Vec<ForeignKey>, ImportError> {
+    let rows: Vec<(String, i64, String, String)> = sqlx::query_as(
+        "SELECT m.name, f.id, f.\"from\", COALESCE(f.\"to\", 'rowid') FROM sqlite_master m, pragma_foreign_key_list(m.name) f
+        WHERE m.type = 'table' AND f.\"table\" = $1 ORDER BY m.name, f.id, f.seq",
+    )
+    .bind(table_name)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| ImportError::database(format!("Unable to look up tables referencing {table_name}"), e))?;
+
+    let mut foreign_keys: Vec<ForeignKey> = Vec::new();
+    for (table, id, from, to) in rows {
+        match foreign_keys.last_mut() {
+            Some(key) if key.table == table && key.id == id => key.columns.push((from, to)),
+            _ => foreign_keys.push(ForeignKey {
+                table,
+                id,
+                columns: vec![(from, to)],
+            }),
+        }
+    }
+
+    Ok(foreign_keys)
+}
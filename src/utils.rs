@@ -1,23 +1,240 @@
-use std::io::{stdout, Write};
+use crate::error::ImportError;
+use flate2::read::MultiGzDecoder;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteQueryResult},
+    Connection, Executor, SqliteConnection,
+};
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::{self, stdout, BufRead, BufReader, Read, Seek, Write},
+    path::Path,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-pub fn percentage_printer(progress: usize, total: usize) {
-    if progress % 10000 != 0 {
-        return;
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Returns the dataset file name as given, or its `.gz` counterpart when only the compressed file exists.
+pub fn resolve_dataset(file_name: &str) -> String {
+    let gz_name = format!("{file_name}.gz");
+    if !Path::new(file_name).exists() && Path::new(&gz_name).exists() {
+        return gz_name;
     }
 
-    let n = progress as f32 / total as f32 * 100.0 + 2.0;
-    let n = n as u8;
-    print!("\r[");
-    for _ in 0..n {
-        print!("#");
+    file_name.to_string()
+}
+
+/// Opens a dataset file for reading, transparently decompressing it when it is gzipped.
+/// Compression is detected from the `.gz` extension or the gzip magic bytes.
+pub fn open_dataset(file_name: &str) -> Result<Box<dyn BufRead + Send>, ImportError> {
+    let mut file = File::open(file_name)
+        .map_err(|e| ImportError::io(format!("Unable to read from {file_name}"), e))?;
+
+    let mut magic = [0u8; 2];
+    let read = file
+        .read(&mut magic)
+        .map_err(|e| ImportError::io(format!("Unable to read from {file_name}"), e))?;
+    let gzipped = file_name.ends_with(".gz") || magic[..read] == GZIP_MAGIC;
+    file.rewind().map_err(|e| {
+        ImportError::io(
+            format!("Failed to read file {file_name} after detecting compression"),
+            e,
+        )
+    })?;
+
+    if gzipped {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(
+            BufReader::new(file),
+        ))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Date the file was last modified, formatted as YYYY-MM-DD in UTC.
+pub fn file_date(file_name: &str) -> Result<String, ImportError> {
+    let modified = fs::metadata(file_name)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| {
+            ImportError::io(
+                format!("Unable to read modification date of {file_name}"),
+                e,
+            )
+        })?;
+    let seconds = modified
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| {
+            ImportError::io(
+                format!("Invalid modification date of {file_name}"),
+                io::Error::other(e),
+            )
+        })?
+        .as_secs();
+
+    Ok(civil_date(seconds / 86400))
+}
+
+/// Formats a point in time as a YYYY-MM-DDTHH:MM:SSZ timestamp in UTC.
+pub fn timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (hour, minute, second) = (seconds % 86400 / 3600, seconds % 3600 / 60, seconds % 60);
+
+    format!(
+        "{}T{hour:02}:{minute:02}:{second:02}Z",
+        civil_date(seconds / 86400)
+    )
+}
+
+/// Converts days since the unix epoch into a YYYY-MM-DD date of the proleptic Gregorian calendar.
+fn civil_date(days: u64) -> String {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Whether the text is a YYYY-MM-DD date that exists in the proleptic Gregorian calendar.
+pub fn is_date(text: &str) -> bool {
+    let parts: Vec<&str> = text.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return false;
+    };
+    if year.len() != 4
+        || month.len() != 2
+        || day.len() != 2
+        || !text.chars().all(|c| c.is_ascii_digit() || c == '-')
+    {
+        return false;
     }
 
-    for _ in n..101 {
-        print!("-");
+    let (Ok(year), Ok(month), Ok(day)) = (
+        year.parse::<u32>(),
+        month.parse::<u32>(),
+        day.parse::<u32>(),
+    ) else {
+        return false;
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+
+    (1..=days).contains(&day)
+}
+
+/// Counts the entries of a dataset file, excluding the header line.
+pub fn count_lines(file_name: &str) -> Result<usize, ImportError> {
+    let mut count = 0;
+    for line in open_dataset(file_name)?.lines().skip(1) {
+        line.map_err(|e| ImportError::io(format!("Failed to count lines of {file_name}"), e))?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Opens the database with foreign key enforcement turned on.
+pub async fn connect(path: &str) -> Result<SqliteConnection, ImportError> {
+    let options = SqliteConnectOptions::from_str(path)
+        .map_err(|e| ImportError::database(format!("Invalid database path {path}"), e))?
+        .create_if_missing(true)
+        .foreign_keys(true);
+    SqliteConnection::connect_with(&options)
+        .await
+        .map_err(|e| ImportError::database(format!("Unable to connect to {path}"), e))
+}
+
+pub async fn table_exists(
+    table_name: &str,
+    conn: &mut SqliteConnection,
+) -> Result<bool, ImportError> {
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = $1)",
+    )
+    .bind(table_name)
+    .fetch_one(conn)
+    .await
+    .map_err(|e| ImportError::database(format!("Unable to look up {table_name} table"), e))
+}
+
+/// Whether the table has the column, false when the table does not exist.
+pub async fn has_column(
+    table_name: &str,
+    column: &str,
+    conn: &mut SqliteConnection,
+) -> Result<bool, ImportError> {
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM pragma_table_info($1) WHERE name = $2)")
+        .bind(table_name)
+        .bind(column)
+        .fetch_one(conn)
+        .await
+        .map_err(|e| ImportError::database(format!("Unable to look up {table_name} table"), e))
+}
+
+/// Runs the statements, separated by semicolons, without preparing them. Going through `Executor::execute` keeps
+/// the future `Send`, which `sqlx::raw_sql(..).execute(..)` does not, so the import can run in a spawned task.
+pub async fn execute_sql(
+    sql: &str,
+    conn: &mut SqliteConnection,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    conn.execute(sqlx::raw_sql(sql)).await
+}
+
+/// Drops the table with foreign keys disabled, otherwise SQLite refuses to drop a table that joining tables still reference.
+pub async fn drop_table(table_name: &str, conn: &mut SqliteConnection) {
+    let _ = execute_sql(
+        format!(
+            "PRAGMA foreign_keys = OFF; DROP TABLE IF EXISTS {table_name}; PRAGMA foreign_keys = ON"
+        )
+        .as_str(),
+        conn,
+    )
+    .await;
+}
+
+pub fn is_foreign_key_violation(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .is_some_and(|e| e.is_foreign_key_violation())
+}
+
+/// Prints a line to stdout. Printing is best effort, a closed stdout must not stop the import.
+pub fn print_line(line: impl Display) {
+    let _ = writeln!(stdout().lock(), "{line}");
+}
+
+/// Prints the progress bar of a dataset every 10000 lines. Printing is best effort, a closed stdout only loses the bar.
+pub fn percentage_printer(progress: usize, total: usize) {
+    if !progress.is_multiple_of(10000) {
+        return;
     }
 
-    print!("] {:02}%", u8::min(n, 100));
-    stdout().flush().unwrap();
+    let n = progress as f32 / total as f32 * 100.0 + 2.0;
+    let n = n as u8;
+    let bar = format!(
+        "\r[{}{}] {:02}%",
+        "#".repeat(n.into()),
+        "-".repeat(101u8.saturating_sub(n).into()),
+        u8::min(n, 100)
+    );
+    let mut stdout = stdout().lock();
+    let _ = stdout
+        .write_all(bar.as_bytes())
+        .and_then(|_| stdout.flush());
 }
 
 pub fn find_strings(str: &str) -> Vec<String> {
@@ -53,7 +270,14 @@ pub fn find_strings(str: &str) -> Vec<String> {
 
 #[cfg(test)]
 mod test {
-    use super::find_strings;
+    use super::{civil_date, count_lines, find_strings, is_date, open_dataset, timestamp};
+    use flate2::{write::GzEncoder, Compression};
+    use std::{
+        fs::File,
+        io::BufRead,
+        io::Write,
+        time::{Duration, UNIX_EPOCH},
+    };
 
     #[test]
     fn test_finding_strings() {
@@ -89,4 +313,48 @@ mod test {
         let expected = vec!["Georges Méliès]"];
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn reads_gzipped_dataset_without_extension() {
+        let path = std::env::temp_dir().join("imdbsql_gzipped_dataset.tsv");
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        encoder
+            .write_all(b"tconst\tprimaryTitle\ntt0000001\tCarmencita\n")
+            .unwrap();
+        encoder.finish().unwrap();
+
+        let file_name = path.to_str().unwrap();
+        let lines: Vec<String> = open_dataset(file_name)
+            .unwrap()
+            .lines()
+            .map(|l| l.unwrap())
+            .collect();
+        assert_eq!(lines, vec!["tconst\tprimaryTitle", "tt0000001\tCarmencita"]);
+        assert_eq!(count_lines(file_name).unwrap(), 1);
+    }
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(11016), "2000-02-29");
+        assert_eq!(civil_date(20744), "2026-10-18");
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        let time = UNIX_EPOCH + Duration::from_secs(20744 * 86400 + 13 * 3600 + 5 * 60 + 9);
+        assert_eq!(timestamp(time), "2026-10-18T13:05:09Z");
+    }
+
+    #[test]
+    fn checks_dates() {
+        assert!(is_date("2024-02-29"));
+        assert!(is_date("2023-12-31"));
+        assert!(!is_date("2023-02-29"));
+        assert!(!is_date("2024-13-01"));
+        assert!(!is_date("2024-1-01"));
+        assert!(!is_date("2024-01-01 "));
+        assert!(!is_date("+024-01-01"));
+        assert!(!is_date("yesterday"));
+    }
 }
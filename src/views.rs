@@ -0,0 +1,135 @@
+use crate::{
+    dataset::Table,
+    error::ImportError,
+    options::TableNames,
+    utils::{execute_sql, has_column},
+};
+use sqlx::SqliteConnection;
+
+/// Foreign key columns of the table joined to expose the IMDb identifiers, as (column, parent, alias).
+/// The alias prefixes the exposed identifier when a table references the same parent twice.
+fn joins(table: Table) -> Vec<(&'static str, Table, Option<&'static str>)> {
+    match table {
+        Table::Name | Table::Title => vec![],
+        Table::NameProfession => vec![("name_id", Table::Name, None)],
+        Table::TitleGenre | Table::TitleAka | Table::TitleRating => {
+            vec![("title_id", Table::Title, None)]
+        }
+        Table::NameTitle
+        | Table::TitleDirector
+        | Table::TitleWriter
+        | Table::TitleJob
+        | Table::TitleCharacter => vec![
+            ("title_id", Table::Title, None),
+            ("name_id", Table::Name, None),
+        ],
+        Table::TitleEpisode => vec![
+            ("title_episode_id", Table::Title, Some("episode")),
+            ("title_series_id", Table::Title, Some("series")),
+        ],
+    }
+}
+
+fn identifier(parent: Table) -> &'static str {
+    match parent {
+        Table::Name => "nconst",
+        _ => "tconst",
+    }
+}
+
+fn view_query(
+    table_name: &str,
+    joins: &[(&str, Table, Option<&str>)],
+    names: &TableNames,
+) -> String {
+    let mut columns = Vec::new();
+    let mut tables = vec![format!("{table_name} t")];
+    for (column, parent, alias) in joins {
+        let identifier = identifier(*parent);
+        let parent_name = names.get(*parent);
+        let (alias, exposed) = match alias {
+            Some(alias) => (alias.to_string(), format!("{alias}_{identifier}")),
+            None => (parent_name.clone(), identifier.to_string()),
+        };
+        columns.push(format!("{alias}.{identifier} AS {exposed}"));
+        tables.push(format!(
+            "JOIN {parent_name} {alias} ON {alias}.id = t.{column}"
+        ));
+    }
+
+    format!(
+        "CREATE VIEW IF NOT EXISTS {table_name}_imdb AS SELECT {}, t.* FROM {}",
+        columns.join(", "),
+        tables.join(" ")
+    )
+}
+
+/// Creates views over the joining table exposing the tconst and nconst identifiers of the rows it references,
+/// which requires the title and name tables to be created with `--imdb-ids`.
+pub async fn create_views(
+    table: Table,
+    names: &TableNames,
+    conn: &mut SqliteConnection,
+) -> Result<(), ImportError> {
+    let joins = joins(table);
+    if joins.is_empty() {
+        return Ok(());
+    }
+
+    let name = names.get(table);
+    // SQLite only resolves the columns of a view once it is queried, so a missing identifier has to be caught here.
+    for (_, parent, _) in &joins {
+        let parent_name = names.get(*parent);
+        if !has_column(&parent_name, identifier(*parent), conn).await? {
+            return Err(ImportError::Schema(format!(
+                "Skipping views on {name} as the {parent_name} table has no {} column, overwrite it with --imdb-ids first",
+                identifier(*parent)
+            )));
+        }
+    }
+
+    let mut table_names = vec![name.clone()];
+    if table == Table::TitleAka {
+        table_names.push(format!("{name}_type"));
+        table_names.push(format!("{name}_attribute"));
+    }
+
+    for table_name in table_names {
+        execute_sql(view_query(&table_name, &joins, names).as_str(), &mut *conn)
+            .await
+            .map_err(|e| {
+                ImportError::database(format!("Unable to create {table_name}_imdb view"), e)
+            })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn aliases_parent_joined_twice() {
+        assert_eq!(
+            view_query(
+                "title_episode",
+                &joins(Table::TitleEpisode),
+                &TableNames::default()
+            ),
+            "CREATE VIEW IF NOT EXISTS title_episode_imdb AS SELECT episode.tconst AS episode_tconst, series.tconst AS series_tconst, t.* FROM title_episode t JOIN title episode ON episode.id = t.title_episode_id JOIN title series ON series.id = t.title_series_id"
+        );
+    }
+
+    #[test]
+    fn joins_renamed_parents() {
+        let names = TableNames {
+            prefix: "imdb_".to_string(),
+            renames: vec![(Table::Name, "person".to_string())],
+        };
+        assert_eq!(
+            view_query("imdb_title_director", &joins(Table::TitleDirector), &names),
+            "CREATE VIEW IF NOT EXISTS imdb_title_director_imdb AS SELECT imdb_title.tconst AS tconst, person.nconst AS nconst, t.* FROM imdb_title_director t JOIN imdb_title imdb_title ON imdb_title.id = t.title_id JOIN person person ON person.id = t.name_id"
+        );
+    }
+}